@@ -0,0 +1,170 @@
+//! Run a soup census from the command line.
+//!
+//! Running it again with the same output file picks up where it left off, skipping any
+//! seeds that are already done.
+
+use std::{
+    fs::{self, File},
+    io::{BufReader, BufWriter, Write},
+    ops::Range,
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+use hexlife::{
+    census::{Census, SoupConfig},
+    math::HexCoord,
+    region::Region,
+    soup::SoupSymmetry,
+    Rule,
+};
+
+const USAGE: &str = "usage: hexlife-census RULE [options]
+
+options:
+    --seeds START..END  which soups to run (default 0..1000)
+    --threads N         how many threads to use (default: all of them)
+    --batch N           how many soups each thread does at a time (default 100)
+    --size N            soups are N by N (default 16)
+    --density P         chance of each edge starting alive (default 0.5)
+    --barren P          chance of each edge starting barren (default 0)
+    --symmetry S        C1, C2, C3, C6, D2, D4, D6 or D12 (default C1)
+    --max-gens N        give up on soups after this many generations (default 2000)
+    --max-pop N         give up on soups with more live edges than this (default 1000)
+    --out FILE          where to keep the census (default census.txt)";
+
+struct Args {
+    config: SoupConfig,
+    seeds: Range<u64>,
+    threads: usize,
+    batch: u64,
+    out: PathBuf,
+}
+
+fn main() -> ExitCode {
+    let args = match parse_args(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("{}\n\n{}", err, USAGE);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let mut census = if args.out.exists() {
+        let census = match File::open(&args.out).and_then(|f| Census::read(BufReader::new(f))) {
+            Ok(census) => census,
+            Err(err) => {
+                eprintln!("couldn't read {}: {}", args.out.display(), err);
+                return ExitCode::FAILURE;
+            }
+        };
+        if !census.matches(&args.config) {
+            eprintln!(
+                "{} was made with different settings; pick another --out",
+                args.out.display()
+            );
+            return ExitCode::FAILURE;
+        }
+        eprintln!("resuming with {} soups done", census.soups());
+        census
+    } else {
+        Census::new(&args.config)
+    };
+
+    let mut failed = None;
+    census.run(
+        &args.config,
+        args.seeds,
+        args.threads,
+        args.batch,
+        |census| {
+            eprintln!(
+                "{} soups, {} unsettled, {} kinds of object",
+                census.soups(),
+                census.unsettled(),
+                census.counts().len()
+            );
+            if let Err(err) = save(census, &args.out) {
+                failed = Some(err);
+            }
+        },
+    );
+    if let Some(err) = failed.or_else(|| save(&census, &args.out).err()) {
+        eprintln!("couldn't write {}: {}", args.out.display(), err);
+        return ExitCode::FAILURE;
+    }
+
+    for (object, count) in census.counts().into_iter().take(20) {
+        println!("{:>8} {}", count, object);
+    }
+    ExitCode::SUCCESS
+}
+
+/// Write to a temporary file and move it over, so getting killed halfway through doesn't
+/// lose the census.
+fn save(census: &Census, path: &Path) -> std::io::Result<()> {
+    let tmp = path.with_extension("tmp");
+    let mut out = BufWriter::new(File::create(&tmp)?);
+    census.write(&mut out)?;
+    out.flush()?;
+    drop(out);
+    fs::rename(tmp, path)
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Args, String> {
+    let rule = args
+        .next()
+        .ok_or("missing rule")?
+        .parse::<Rule>()
+        .map_err(|err| err.to_string())?;
+    let mut out = Args {
+        config: SoupConfig::new(rule),
+        seeds: 0..1000,
+        threads: std::thread::available_parallelism().map_or(1, |n| n.get()),
+        batch: 100,
+        out: PathBuf::from("census.txt"),
+    };
+
+    while let Some(flag) = args.next() {
+        let value = args
+            .next()
+            .ok_or_else(|| format!("{} needs a value", flag))?;
+        let bad = || format!("bad value for {}: {}", flag, value);
+        match flag.as_str() {
+            "--seeds" => {
+                let (start, end) = value.split_once("..").ok_or_else(bad)?;
+                out.seeds = start.parse().map_err(|_| bad())?..end.parse().map_err(|_| bad())?;
+            }
+            "--threads" => out.threads = value.parse().map_err(|_| bad())?,
+            "--batch" => out.batch = value.parse().map_err(|_| bad())?,
+            "--size" => {
+                let size = value.parse().map_err(|_| bad())?;
+                out.config.region = Region::Parallelogram {
+                    corner: HexCoord::new(0, 0),
+                    width: size,
+                    height: size,
+                };
+            }
+            "--density" => out.config.density.alive = value.parse().map_err(|_| bad())?,
+            "--barren" => out.config.density.barren = value.parse().map_err(|_| bad())?,
+            "--symmetry" => {
+                out.config.symmetry = match value.as_str() {
+                    "C1" => SoupSymmetry::None,
+                    "C2" => SoupSymmetry::C2,
+                    "C3" => SoupSymmetry::C3,
+                    "C6" => SoupSymmetry::C6,
+                    "D2" => SoupSymmetry::D2,
+                    "D4" => SoupSymmetry::D4,
+                    "D6" => SoupSymmetry::D6,
+                    "D12" => SoupSymmetry::D12,
+                    _ => return Err(bad()),
+                }
+            }
+            "--max-gens" => out.config.max_generations = value.parse().map_err(|_| bad())?,
+            "--max-pop" => out.config.max_population = value.parse().map_err(|_| bad())?,
+            "--out" => out.out = PathBuf::from(value),
+            _ => return Err(format!("unknown option {}", flag)),
+        }
+    }
+    Ok(out)
+}
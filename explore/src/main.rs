@@ -0,0 +1,253 @@
+//! Survey a whole neighborhood's worth of rules from the command line, or breed rules
+//! with lots of spaceships.
+//!
+//! Surveys write a tab-separated table with one rule per line, sorted by whichever column
+//! you ask for. Evolving writes a log of every round.
+
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::PathBuf,
+    process::ExitCode,
+};
+
+use hexlife::{
+    evolve::{Evolution, EvolveConfig},
+    explore::{self, ExploreConfig, COLUMNS},
+    math::HexCoord,
+    region::Region,
+    NeighborRegion, Rule,
+};
+
+const USAGE: &str = "usage: hexlife-explore NEIGHBORHOOD [options]
+       hexlife-explore evolve [options]
+
+NEIGHBORHOOD is 4, 6, 8*, 8= or 10.
+
+options for both:
+    --seed N            for picking rules and making soups (default 0)
+    --soups N           how many soups each rule gets (default 8)
+    --gens N            how long to run each soup (default 500)
+    --size N            soups are N by N (default 16)
+    --density P         chance of each edge starting alive (default 0.5)
+    --max-pop N         soups with this many live edges count as exploding (default 5000)
+    --threads N         how many threads to use (default: all of them)
+
+options for surveys:
+    --sample N          try N random rules instead of all of them
+    --sort COLUMN       sort by this column, biggest first (default activity)
+    --out FILE          where to write the table (default: stdout)
+
+options for evolving:
+    --rounds N          how many rounds to run (default 20)
+    --population N      rules per round (default 24)
+    --survivors N       best rules kept each round (default 6)
+    --neighborhoods L   comma-separated neighborhoods to use (default all of them)
+    --start RULES       comma-separated rules to start from instead of random ones
+    --log FILE          where to write the log (default: stdout)";
+
+enum Mode {
+    Survey {
+        neighbors: NeighborRegion,
+        sample: Option<usize>,
+        sort: String,
+    },
+    Evolve {
+        config: EvolveConfig,
+        rounds: u64,
+        start: Vec<Rule>,
+    },
+}
+
+struct Args {
+    mode: Mode,
+    soups: ExploreConfig,
+    threads: usize,
+    out: Option<PathBuf>,
+}
+
+fn main() -> ExitCode {
+    let args = match parse_args(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("{}\n\n{}", err, USAGE);
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let out: Box<dyn Write> = match &args.out {
+        Some(path) => match File::create(path) {
+            Ok(file) => Box::new(BufWriter::new(file)),
+            Err(err) => {
+                eprintln!("couldn't create {}: {}", path.display(), err);
+                return ExitCode::FAILURE;
+            }
+        },
+        None => Box::new(io::stdout().lock()),
+    };
+    let result = match args.mode {
+        Mode::Survey {
+            neighbors,
+            sample,
+            sort,
+        } => survey(neighbors, sample, &sort, &args.soups, args.threads, out),
+        Mode::Evolve {
+            mut config,
+            rounds,
+            start,
+        } => {
+            config.soups = args.soups;
+            evolve(config, rounds, &start, args.threads, out)
+        }
+    };
+    if let Err(err) = result {
+        eprintln!("couldn't write the output: {}", err);
+        return ExitCode::FAILURE;
+    }
+    ExitCode::SUCCESS
+}
+
+fn survey(
+    neighbors: NeighborRegion,
+    sample: Option<usize>,
+    sort: &str,
+    config: &ExploreConfig,
+    threads: usize,
+    mut out: impl Write,
+) -> io::Result<()> {
+    let rules = match sample {
+        Some(count) => explore::sample_rules(neighbors, count, config.seed),
+        None => explore::all_rules(neighbors).collect(),
+    };
+    let mut done = 0;
+    let mut reports = explore::explore(&rules, config, threads, |_| {
+        done += 1;
+        if done % 100 == 0 || done == rules.len() {
+            eprintln!("{}/{} rules", done, rules.len());
+        }
+    });
+    reports.sort_by(|a, b| {
+        let (a, b) = (a.column(sort), b.column(sort));
+        b.partial_cmp(&a).unwrap_or(std::cmp::Ordering::Equal)
+    });
+    explore::write_table(&reports, &mut out)?;
+    out.flush()
+}
+
+fn evolve(
+    config: EvolveConfig,
+    rounds: u64,
+    start: &[Rule],
+    threads: usize,
+    mut out: impl Write,
+) -> io::Result<()> {
+    let mut evolution = Evolution::with_rules(config, start);
+    evolution.write_header(&mut out)?;
+    for _ in 0..rounds {
+        evolution.step(threads);
+        evolution.write_round(&mut out)?;
+        out.flush()?;
+        if let Some(best) = evolution.best() {
+            eprintln!(
+                "round {}: best {} with {} kinds of spaceship",
+                evolution.round(),
+                best.report.rule,
+                best.report.spaceship_kinds
+            );
+        }
+    }
+    Ok(())
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Args, String> {
+    let first = args.next().ok_or("missing neighborhood")?;
+    let mode = if first == "evolve" {
+        Mode::Evolve {
+            config: EvolveConfig::default(),
+            rounds: 20,
+            start: Vec::new(),
+        }
+    } else {
+        Mode::Survey {
+            neighbors: first
+                .parse()
+                .map_err(|err: hexlife::ParseRuleError| err.to_string())?,
+            sample: None,
+            sort: "activity".to_string(),
+        }
+    };
+    let mut out = Args {
+        mode,
+        soups: ExploreConfig::default(),
+        threads: std::thread::available_parallelism().map_or(1, |n| n.get()),
+        out: None,
+    };
+
+    while let Some(flag) = args.next() {
+        let value = args
+            .next()
+            .ok_or_else(|| format!("{} needs a value", flag))?;
+        let bad = || format!("bad value for {}: {}", flag, value);
+        match (flag.as_str(), &mut out.mode) {
+            ("--seed", mode) => {
+                out.soups.seed = value.parse().map_err(|_| bad())?;
+                if let Mode::Evolve { config, .. } = mode {
+                    config.seed = out.soups.seed;
+                }
+            }
+            ("--soups", _) => out.soups.soups = value.parse().map_err(|_| bad())?,
+            ("--gens", _) => out.soups.generations = value.parse().map_err(|_| bad())?,
+            ("--size", _) => {
+                let size = value.parse().map_err(|_| bad())?;
+                out.soups.region = Region::Parallelogram {
+                    corner: HexCoord::new(0, 0),
+                    width: size,
+                    height: size,
+                };
+            }
+            ("--density", _) => out.soups.density.alive = value.parse().map_err(|_| bad())?,
+            ("--max-pop", _) => out.soups.max_population = value.parse().map_err(|_| bad())?,
+            ("--threads", _) => out.threads = value.parse().map_err(|_| bad())?,
+            ("--out" | "--log", _) => out.out = Some(PathBuf::from(value)),
+            ("--sample", Mode::Survey { sample, .. }) => {
+                *sample = Some(value.parse().map_err(|_| bad())?)
+            }
+            ("--sort", Mode::Survey { sort, .. }) => {
+                if !COLUMNS.contains(&value.as_str()) {
+                    return Err(format!(
+                        "can't sort by {}; columns are {}",
+                        value,
+                        COLUMNS.join(", ")
+                    ));
+                }
+                *sort = value;
+            }
+            ("--rounds", Mode::Evolve { rounds, .. }) => {
+                *rounds = value.parse().map_err(|_| bad())?
+            }
+            ("--population", Mode::Evolve { config, .. }) => {
+                config.population = value.parse().map_err(|_| bad())?
+            }
+            ("--survivors", Mode::Evolve { config, .. }) => {
+                config.survivors = value.parse().map_err(|_| bad())?
+            }
+            ("--neighborhoods", Mode::Evolve { config, .. }) => {
+                config.neighborhoods = value
+                    .split(',')
+                    .map(|neighbors| neighbors.parse().map_err(|_| bad()))
+                    .collect::<Result<_, _>>()?;
+                if config.neighborhoods.is_empty() {
+                    return Err(bad());
+                }
+            }
+            ("--start", Mode::Evolve { start, .. }) => {
+                *start = value
+                    .split(',')
+                    .map(|rule| rule.parse().map_err(|_| bad()))
+                    .collect::<Result<_, _>>()?;
+            }
+            _ => return Err(format!("unknown option {}", flag)),
+        }
+    }
+    Ok(out)
+}
@@ -0,0 +1,190 @@
+//! Tracking which parts of the board are actually doing anything.
+//!
+//! The board is cut up into square chunks in axial coordinates. Whenever an edge
+//! changes, by stepping or by editing, its chunk gets marked, and the next step only
+//! recomputes chunks close enough to a marked one to be affected.
+
+use ahash::AHashSet;
+
+use crate::{
+    math::HexCoord,
+    transition::{TableKey, TransitionTable},
+};
+
+/// How many hexes wide (in axial coordinates) a chunk is.
+///
+/// This has to be at least as big as the furthest a neighbor can be.
+pub const CHUNK_SIZE: i64 = 16;
+
+/// Get the position of the chunk containing the coordinate.
+///
+/// Chunk `(x, y)` contains all the coordinates from `(x * CHUNK_SIZE, y * CHUNK_SIZE)`
+/// up to but not including `((x + 1) * CHUNK_SIZE, (y + 1) * CHUNK_SIZE)`.
+pub fn chunk_of(coord: HexCoord) -> HexCoord {
+    HexCoord::new(
+        coord.x.div_euclid(CHUNK_SIZE),
+        coord.y.div_euclid(CHUNK_SIZE),
+    )
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ActivityTracker {
+    /// Chunks that changed in the last generation, or have been edited since.
+    changed: AHashSet<HexCoord>,
+    /// Chunks that changed in the generation before that.
+    previous: AHashSet<HexCoord>,
+    /// What the last step used. Quiet chunks only stay quiet under the same rule.
+    table: Option<TableKey>,
+}
+
+impl ActivityTracker {
+    /// Chunks that changed in the last generation, or have been edited since.
+    pub fn active_chunks(&self) -> impl Iterator<Item = HexCoord> + '_ {
+        self.changed.iter().copied()
+    }
+
+    /// Chunks that changed in the generation before the last one.
+    ///
+    /// Something that's active in both is probably an oscillator; something that's only
+    /// active here just settled down.
+    pub fn previously_active_chunks(&self) -> impl Iterator<Item = HexCoord> + '_ {
+        self.previous.iter().copied()
+    }
+
+    pub fn is_active(&self, chunk: HexCoord) -> bool {
+        self.changed.contains(&chunk)
+    }
+
+    pub fn was_active(&self, chunk: HexCoord) -> bool {
+        self.previous.contains(&chunk)
+    }
+
+    /// Note that something in the chunk containing this coordinate changed.
+    pub(crate) fn mark(&mut self, coord: HexCoord) {
+        self.changed.insert(chunk_of(coord));
+    }
+
+    /// Note which table the next step uses, returning whether it's different from the last
+    /// one, in which case everything needs recomputing.
+    pub(crate) fn switch_table(&mut self, table: &TransitionTable) -> bool {
+        let key = Some(table.key());
+        if self.table == key {
+            return false;
+        }
+        self.table = key;
+        true
+    }
+
+    /// Start a new generation, returning what changed in the last one.
+    pub(crate) fn begin_step(&mut self) -> AHashSet<HexCoord> {
+        self.previous = std::mem::take(&mut self.changed);
+        self.previous.clone()
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.changed.clear();
+        self.previous.clear();
+    }
+}
+
+/// Every chunk within one chunk of the given ones.
+pub(crate) fn dilate(chunks: &AHashSet<HexCoord>) -> AHashSet<HexCoord> {
+    let mut out = AHashSet::with_capacity(chunks.len() * 9);
+    for chunk in chunks {
+        for dy in -1..=1 {
+            for dx in -1..=1 {
+                out.insert(*chunk + HexCoord::new(dx, dy));
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use crate::{
+        evolve::ALL_NEIGHBORHOODS,
+        explore::random_rule,
+        math::{Aliveness, EdgePos, HexCoord, RestrictedHexDir},
+        random::Rng,
+        region::Region,
+        soup::{Density, SoupSymmetry},
+        transition::TransitionTable,
+        Board, NeighborRegion, Rule,
+    };
+
+    /// The same board built from scratch, so its next step looks at everything.
+    fn recomputed(board: &Board) -> Board {
+        let mut out = Board::new();
+        for (pos, alive) in board.edges() {
+            out.set_alive(pos, alive);
+        }
+        out
+    }
+
+    #[test]
+    fn rule_change_wakes_everything() {
+        let mut board = Board::new();
+        board.set_alive(
+            EdgePos::new_raw(HexCoord::new(0, 0), RestrictedHexDir::XY),
+            Aliveness::Alive,
+        );
+        for _ in 0..3 {
+            board.apply_rule(Rule::new_raw(0, 1, NeighborRegion::Six));
+        }
+        let mut full = recomputed(&board);
+        board.apply_rule(Rule::new_raw(0, 0, NeighborRegion::Six));
+        full.apply_rule(Rule::new_raw(0, 0, NeighborRegion::Six));
+        assert_eq!(full.population(), 0);
+        assert!(board == full);
+    }
+
+    #[test]
+    fn rules_only_get_compiled_when_they_change() {
+        let rule = Rule::new_raw(0b100, 0b1000, NeighborRegion::Six);
+        assert_eq!(rule.compile().key(), rule.compile().key());
+        let custom = || TransitionTable::from_fn(NeighborRegion::Six, |_, state, _| state);
+        assert_ne!(custom().key(), custom().key());
+
+        let mut board = Board::new();
+        board.apply_rule(rule);
+        let first = board.compiled.clone().unwrap();
+        board.apply_rule(rule);
+        assert!(Arc::ptr_eq(&first, board.compiled.as_ref().unwrap()));
+        board.apply_rule(Rule::new_raw(0b100, 0b100, NeighborRegion::Six));
+        assert!(!Arc::ptr_eq(&first, board.compiled.as_ref().unwrap()));
+    }
+
+    #[test]
+    fn tracked_steps_match_full_recomputation() {
+        let mut rng = Rng::new(0);
+        let region = Region::Parallelogram {
+            corner: HexCoord::new(0, 0),
+            width: 12,
+            height: 12,
+        };
+        for seed in 0..8 {
+            let mut board = Board::new();
+            board.random_fill(&region, Density::default(), SoupSymmetry::None, seed);
+            let mut rule = random_rule(NeighborRegion::Six, &mut rng);
+            for generation in 0..25 {
+                if generation % 10 == 5 {
+                    let neighbors = ALL_NEIGHBORHOODS[rng.below(5) as usize];
+                    rule = random_rule(neighbors, &mut rng);
+                }
+                let mut full = recomputed(&board);
+                board.apply_rule(rule);
+                full.apply_rule(rule);
+                assert!(
+                    board == full,
+                    "seed {} generation {} under {}",
+                    seed,
+                    generation,
+                    rule
+                );
+            }
+        }
+    }
+}
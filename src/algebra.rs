@@ -0,0 +1,99 @@
+//! Comparing boards and combining them like sets of edges.
+//!
+//! For the set-like operations, an edge is "in" a board if it's alive or barren. When both
+//! boards have an edge and they disagree on what state it's in, alive beats barren for
+//! [`Board::union`] and barren beats alive for [`Board::intersection`].
+
+use ahash::AHashSet;
+
+use crate::{
+    math::{Aliveness, EdgePos},
+    Board,
+};
+
+/// One edge that's different between two boards.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct EdgeChange {
+    pub pos: EdgePos,
+    pub before: Aliveness,
+    pub after: Aliveness,
+}
+
+impl Board {
+    /// Every edge that's in a different state on `other`, in no particular order.
+    ///
+    /// `before` is the state on this board and `after` is the state on `other`.
+    pub fn diff(&self, other: &Board) -> Vec<EdgeChange> {
+        let positions = self
+            .edges()
+            .chain(other.edges())
+            .map(|(pos, _)| pos)
+            .collect::<AHashSet<_>>();
+        positions
+            .into_iter()
+            .filter_map(|pos| {
+                let before = self.get_liveness(pos);
+                let after = other.get_liveness(pos);
+                (before != after).then_some(EdgeChange { pos, before, after })
+            })
+            .collect()
+    }
+
+    /// Every edge that's on either board.
+    pub fn union(&self, other: &Board) -> Board {
+        let mut out = self.clone();
+        for (pos, alive) in other.edges() {
+            if strength(alive) > strength(out.get_liveness(pos)) {
+                out.set_alive(pos, alive);
+            }
+        }
+        out
+    }
+
+    /// Every edge that's on both boards.
+    pub fn intersection(&self, other: &Board) -> Board {
+        let mut out = Board::new();
+        for (pos, alive) in self.edges() {
+            let theirs = other.get_liveness(pos);
+            if theirs != Aliveness::Dead {
+                let weaker = if strength(theirs) < strength(alive) {
+                    theirs
+                } else {
+                    alive
+                };
+                out.set_alive(pos, weaker);
+            }
+        }
+        out
+    }
+
+    /// Every edge that's on this board but not the other one.
+    pub fn difference(&self, other: &Board) -> Board {
+        let mut out = Board::new();
+        for (pos, alive) in self.edges() {
+            if other.get_liveness(pos) == Aliveness::Dead {
+                out.set_alive(pos, alive);
+            }
+        }
+        out
+    }
+
+    /// Every edge that's on exactly one of the boards, in whatever state it's in there.
+    pub fn symmetric_difference(&self, other: &Board) -> Board {
+        let mut out = self.difference(other);
+        for (pos, alive) in other.edges() {
+            if self.get_liveness(pos) == Aliveness::Dead {
+                out.set_alive(pos, alive);
+            }
+        }
+        out
+    }
+}
+
+fn strength(alive: Aliveness) -> u8 {
+    match alive {
+        Aliveness::Dead => 0,
+        Aliveness::Barren => 1,
+        Aliveness::Alive => 2,
+    }
+}
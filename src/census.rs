@@ -0,0 +1,634 @@
+//! Running lots of random soups and counting what comes out, like apgsearch does.
+//!
+//! Each soup gets run until it settles down. Then whatever's left gets split up into
+//! separate objects, each object gets run on its own to figure out what it is, and it
+//! gets turned to face a canonical way so every copy of it gets counted together.
+
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    io::{self, BufRead, Write},
+    ops::Range,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc,
+    },
+};
+
+use ahash::AHashMap;
+
+use crate::{
+    components::{Adjacency, DisjointSets},
+    math::{Aliveness, HexCoord},
+    pattern::Pattern,
+    region::Region,
+    simulation::Simulation,
+    soup::{Density, SoupSymmetry},
+    stop::Outcome,
+    transition::TransitionTable,
+    Board, Rule,
+};
+
+/// How long to check that objects in a soup that never settled down aren't interacting.
+pub(crate) const UNSETTLED_WINDOW: u64 = 64;
+
+/// What kind of thing an object is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Class {
+    /// Doesn't change.
+    Still,
+    /// Comes back to the same place after `period` generations.
+    Oscillator { period: u64 },
+    /// Comes back moved over by `displacement` after `period` generations.
+    Spaceship { period: u64, displacement: HexCoord },
+}
+
+impl Class {
+    pub fn period(&self) -> u64 {
+        match self {
+            Class::Still => 1,
+            Class::Oscillator { period } | Class::Spaceship { period, .. } => *period,
+        }
+    }
+}
+
+impl Display for Class {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Class::Still => write!(f, "still"),
+            Class::Oscillator { period } => write!(f, "p{}", period),
+            Class::Spaceship {
+                period,
+                displacement,
+            } => write!(f, "c{}({},{})", period, displacement.x, displacement.y),
+        }
+    }
+}
+
+/// An object that's been figured out and put in canonical form.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Object {
+    pub class: Class,
+    /// The phase and orientation that comes out smallest, with its smallest coordinate at
+    /// the origin. For spaceships, the displacement is for this orientation.
+    pub pattern: Pattern,
+}
+
+impl Display for Object {
+    /// Writes out its [code](crate::code).
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.code())
+    }
+}
+
+/// How to make and run soups.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SoupConfig {
+    pub rule: Rule,
+    pub region: Region,
+    pub density: Density,
+    pub symmetry: SoupSymmetry,
+    /// Give up on a soup, or an object in it, settling down after this many generations.
+    pub max_generations: u64,
+    /// Give up on a soup once it has this many live edges.
+    pub max_population: u64,
+}
+
+impl SoupConfig {
+    /// 16x16 soups at half density, like apgsearch.
+    pub fn new(rule: Rule) -> Self {
+        Self {
+            rule,
+            region: Region::Parallelogram {
+                corner: HexCoord::new(0, 0),
+                width: 16,
+                height: 16,
+            },
+            density: Density::default(),
+            symmetry: SoupSymmetry::None,
+            max_generations: 2000,
+            max_population: 1000,
+        }
+    }
+}
+
+/// What came out of one soup.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SoupResult {
+    pub objects: Vec<Object>,
+    /// False if the soup or some object in it never settled down, or it got too big.
+    pub settled: bool,
+}
+
+/// Make the soup for a seed, run it, and figure out what's left.
+pub fn run_soup(config: &SoupConfig, seed: u64) -> SoupResult {
+    let mut board = Board::new();
+    board.random_fill(&config.region, config.density, config.symmetry, seed);
+    let mut sim = Simulation::new(board, config.rule);
+    sim.run_until(config.max_generations, |sim| {
+        sim.outcome().is_settled() || sim.stats().population > config.max_population
+    });
+    if sim.stats().population > config.max_population {
+        return SoupResult {
+            objects: Vec::new(),
+            settled: false,
+        };
+    }
+    objects_in(
+        sim.board(),
+        sim.outcome(),
+        config.rule,
+        config.max_generations,
+    )
+}
+
+/// Split up and classify what's left on a board that ran until `outcome`.
+pub(crate) fn objects_in(
+    board: &Board,
+    outcome: Outcome,
+    rule: Rule,
+    max_generations: u64,
+) -> SoupResult {
+    let window = match outcome {
+        Outcome::Extinct { .. } => {
+            return SoupResult {
+                objects: Vec::new(),
+                settled: true,
+            }
+        }
+        Outcome::Stable { .. } => 1,
+        Outcome::Periodic { period, .. } | Outcome::Moving { period, .. } => period,
+        // It might just be spaceships flying away from everything else, so it counts as
+        // settled if every piece does
+        Outcome::Running => UNSETTLED_WINDOW,
+    };
+
+    let table = rule.compile();
+    let mut objects = Vec::new();
+    let mut settled = true;
+    for part in separate(board, &table, window) {
+        // Leftover barren edges are about to vanish anyways
+        if part.population() == 0 {
+            continue;
+        }
+        match classify(&part, rule, max_generations) {
+            Some(object) => objects.push(object),
+            None => settled = false,
+        }
+    }
+    SoupResult { objects, settled }
+}
+
+/// Split a board up into objects that don't affect each other.
+///
+/// Starts with [`Board::components`] by neighborhood, then runs each piece on its own
+/// for `window` generations, and lumps together pieces that turn out to interact.
+pub fn separate(board: &Board, table: &TransitionTable, window: u64) -> Vec<Pattern> {
+    let mut groups = board
+        .components(Adjacency::Neighbors(table.neighbors()), true)
+        .into_iter()
+        .map(|component| component.pattern.to_board())
+        .collect::<Vec<_>>();
+
+    let mut actual = vec![board.clone()];
+    for _ in 0..window {
+        let mut next = actual.last().unwrap().clone();
+        next.apply_table(table);
+        actual.push(next);
+    }
+
+    'check: loop {
+        let mut evolving = groups.clone();
+        for actual in actual.iter().skip(1) {
+            let previous = evolving.clone();
+            let mut together = Board::new();
+            for group in evolving.iter_mut() {
+                group.apply_table(table);
+                for (pos, alive) in group.edges() {
+                    if together.get_liveness(pos) != Aliveness::Alive {
+                        together.set_alive(pos, alive);
+                    }
+                }
+            }
+            let mismatches = together.diff(actual);
+            if mismatches.is_empty() {
+                continue;
+            }
+
+            // Everything that was at or feeding into a mismatched edge has to be one object
+            let mut sets = DisjointSets::new(groups.len());
+            let mut merged = false;
+            for change in mismatches {
+                let pos = change.pos;
+                let sources = table.sources(pos).chain(std::iter::once(pos));
+                let mut involved = Vec::new();
+                for source in sources {
+                    for (idx, group) in previous.iter().enumerate() {
+                        if group.get_liveness(source) != Aliveness::Dead {
+                            involved.push(idx);
+                        }
+                    }
+                }
+                for pair in involved.windows(2) {
+                    merged |= sets.join(pair[0], pair[1]);
+                }
+            }
+            if !merged {
+                // Shouldn't happen, but this is always right
+                return vec![Pattern::from_board(board)];
+            }
+
+            let mut lumped = BTreeMap::<usize, Board>::new();
+            for (idx, group) in groups.into_iter().enumerate() {
+                let lump = lumped.entry(sets.find(idx)).or_default();
+                *lump = lump.union(&group);
+            }
+            groups = lumped.into_values().collect();
+            continue 'check;
+        }
+        break;
+    }
+
+    groups.iter().map(Pattern::from_board).collect()
+}
+
+/// Run a pattern on its own to figure out what it is, and put it in canonical form.
+///
+/// Returns `None` if it dies or doesn't settle down within `max_generations`.
+pub fn classify(pattern: &Pattern, rule: Rule, max_generations: u64) -> Option<Object> {
+    let mut sim = Simulation::new(pattern.to_board(), rule);
+    let class = match sim.run_until_settled(max_generations) {
+        Outcome::Stable { .. } => Class::Still,
+        Outcome::Periodic { period, .. } => Class::Oscillator { period },
+        Outcome::Moving {
+            period,
+            displacement,
+            ..
+        } => Class::Spaceship {
+            period,
+            displacement,
+        },
+        Outcome::Extinct { .. } | Outcome::Running => return None,
+    };
+
+    // Pick whichever phase comes out smallest
+    let mut best = None::<(Vec<(i64, i64, u8)>, Board, _)>;
+    for _ in 0..class.period() {
+        let (board, transform) = sim.board().canonical();
+        let key = board.sorted_cells();
+        if best.as_ref().is_none_or(|(best_key, ..)| key < *best_key) {
+            best = Some((key, board, transform));
+        }
+        sim.step();
+    }
+    let (_, board, transform) = best.expect("everything has a period of at least 1");
+
+    let class = match class {
+        Class::Spaceship {
+            period,
+            displacement,
+        } => Class::Spaceship {
+            period,
+            displacement: transform.linear().apply_coord(displacement),
+        },
+        class => class,
+    };
+    Some(Object {
+        class,
+        pattern: Pattern::from_board(&board),
+    })
+}
+
+/// Running totals of what's come out of a bunch of soups.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Census {
+    /// The rule and soup settings, so resuming a census with different ones is an error.
+    description: String,
+    soups: u64,
+    unsettled: u64,
+    /// Sorted, and never touching each other.
+    seeds: Vec<Range<u64>>,
+    counts: AHashMap<String, u64>,
+}
+
+impl Census {
+    pub fn new(config: &SoupConfig) -> Self {
+        Self {
+            description: description(config),
+            ..Default::default()
+        }
+    }
+
+    /// Was this census made with these settings?
+    pub fn matches(&self, config: &SoupConfig) -> bool {
+        self.description == description(config)
+    }
+
+    /// How many soups have been run.
+    pub fn soups(&self) -> u64 {
+        self.soups
+    }
+
+    /// How many soups didn't settle down all the way.
+    pub fn unsettled(&self) -> u64 {
+        self.unsettled
+    }
+
+    /// Ranges of seeds that have been done already, sorted.
+    pub fn seeds_done(&self) -> &[Range<u64>] {
+        &self.seeds
+    }
+
+    /// The parts of `seeds` that haven't been done yet.
+    pub fn remaining(&self, seeds: Range<u64>) -> Vec<Range<u64>> {
+        let mut out = Vec::new();
+        let mut start = seeds.start;
+        for done in self.seeds.iter() {
+            if done.end <= start {
+                continue;
+            }
+            if done.start >= seeds.end {
+                break;
+            }
+            if done.start > start {
+                out.push(start..done.start);
+            }
+            start = start.max(done.end);
+        }
+        if start < seeds.end {
+            out.push(start..seeds.end);
+        }
+        out
+    }
+
+    /// The [code](crate::code) of every object seen and how many times, most common first.
+    pub fn counts(&self) -> Vec<(&str, u64)> {
+        let mut out = self
+            .counts
+            .iter()
+            .map(|(object, &count)| (object.as_str(), count))
+            .collect::<Vec<_>>();
+        out.sort_unstable_by(|(lhs, lhs_count), (rhs, rhs_count)| {
+            rhs_count.cmp(lhs_count).then(lhs.cmp(rhs))
+        });
+        out
+    }
+
+    /// Count what came out of the soup with this seed.
+    pub fn add_soup(&mut self, seed: u64, result: &SoupResult) {
+        self.soups += 1;
+        if !result.settled {
+            self.unsettled += 1;
+        }
+        for object in result.objects.iter() {
+            *self.counts.entry(object.code()).or_default() += 1;
+        }
+        self.mark_done(seed..seed + 1);
+    }
+
+    /// Add everything from another census into this one.
+    pub fn merge(&mut self, other: &Census) {
+        self.soups += other.soups;
+        self.unsettled += other.unsettled;
+        for (object, count) in other.counts.iter() {
+            *self.counts.entry(object.clone()).or_default() += count;
+        }
+        for seeds in other.seeds.iter() {
+            self.mark_done(seeds.clone());
+        }
+    }
+
+    fn mark_done(&mut self, seeds: Range<u64>) {
+        if seeds.is_empty() {
+            return;
+        }
+        self.seeds.push(seeds);
+        self.seeds.sort_unstable_by_key(|seeds| seeds.start);
+        let mut merged: Vec<Range<u64>> = Vec::with_capacity(self.seeds.len());
+        for seeds in self.seeds.drain(..) {
+            match merged.last_mut() {
+                Some(last) if seeds.start <= last.end => last.end = last.end.max(seeds.end),
+                _ => merged.push(seeds),
+            }
+        }
+        self.seeds = merged;
+    }
+
+    /// Run every seed in the range that hasn't been done yet, on `threads` threads.
+    ///
+    /// Seeds get handed out `batch` at a time, and `checkpoint` gets called with the
+    /// totals so far every time a batch finishes.
+    pub fn run(
+        &mut self,
+        config: &SoupConfig,
+        seeds: Range<u64>,
+        threads: usize,
+        batch: u64,
+        mut checkpoint: impl FnMut(&Census),
+    ) {
+        let batches = self
+            .remaining(seeds)
+            .into_iter()
+            .flat_map(|range| {
+                let batch = batch.max(1);
+                (range.start..range.end)
+                    .step_by(batch as usize)
+                    .map(move |start| start..(start + batch).min(range.end))
+            })
+            .collect::<Vec<_>>();
+        let next = AtomicU64::new(0);
+
+        std::thread::scope(|scope| {
+            let (tx, rx) = mpsc::channel();
+            for _ in 0..threads.max(1) {
+                let tx = tx.clone();
+                let (batches, next) = (&batches, &next);
+                scope.spawn(move || loop {
+                    let idx = next.fetch_add(1, Ordering::Relaxed) as usize;
+                    let Some(seeds) = batches.get(idx) else {
+                        break;
+                    };
+                    let mut partial = Census::new(config);
+                    for seed in seeds.clone() {
+                        partial.add_soup(seed, &run_soup(config, seed));
+                    }
+                    if tx.send(partial).is_err() {
+                        break;
+                    }
+                });
+            }
+            drop(tx);
+            for partial in rx {
+                self.merge(&partial);
+                checkpoint(self);
+            }
+        });
+    }
+
+    /// Write the census out in a format [`Census::read`] understands.
+    pub fn write(&self, mut out: impl Write) -> io::Result<()> {
+        writeln!(out, "# hexlife census")?;
+        writeln!(out, "soup {}", self.description)?;
+        writeln!(out, "soups {}", self.soups)?;
+        writeln!(out, "unsettled {}", self.unsettled)?;
+        let seeds = self
+            .seeds
+            .iter()
+            .map(|seeds| format!("{}..{}", seeds.start, seeds.end))
+            .collect::<Vec<_>>();
+        writeln!(out, "seeds {}", seeds.join(" "))?;
+        for (object, count) in self.counts() {
+            writeln!(out, "{} {}", count, object)?;
+        }
+        Ok(())
+    }
+
+    /// Read back a census written with [`Census::write`].
+    pub fn read(input: impl BufRead) -> io::Result<Census> {
+        let bad = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_string());
+        let number = |s: &str| s.parse::<u64>().map_err(|_| bad("expected a number"));
+
+        let mut census = Census::default();
+        for line in input.lines() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, rest) = line.split_once(' ').unwrap_or((line, ""));
+            match key {
+                "soup" => census.description = rest.to_string(),
+                "soups" => census.soups = number(rest)?,
+                "unsettled" => census.unsettled = number(rest)?,
+                "seeds" => {
+                    for range in rest.split_whitespace() {
+                        let (start, end) = range
+                            .split_once("..")
+                            .ok_or_else(|| bad("expected a seed range"))?;
+                        census.mark_done(number(start)?..number(end)?);
+                    }
+                }
+                count => {
+                    *census.counts.entry(rest.to_string()).or_default() += number(count)?;
+                }
+            }
+        }
+        Ok(census)
+    }
+}
+
+/// Everything about the soups, on one line.
+fn description(config: &SoupConfig) -> String {
+    let region = match &config.region {
+        Region::Hexagon { center, radius } => {
+            format!("hexagon({},{};{})", center.x, center.y, radius)
+        }
+        Region::Parallelogram {
+            corner,
+            width,
+            height,
+        } => format!(
+            "parallelogram({},{};{}x{})",
+            corner.x, corner.y, width, height
+        ),
+        Region::Coords(coords) => {
+            let mut coords = coords.iter().collect::<Vec<_>>();
+            coords.sort_unstable();
+            let coords = coords
+                .iter()
+                .map(|coord| format!("{},{}", coord.x, coord.y))
+                .collect::<Vec<_>>();
+            format!("coords({})", coords.join(";"))
+        }
+    };
+    format!(
+        "{} {} alive={} barren={} symmetry={:?} max_generations={} max_population={}",
+        config.rule,
+        region,
+        config.density.alive,
+        config.density.barren,
+        config.symmetry,
+        config.max_generations,
+        config.max_population
+    )
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{code, pattern::StampMode, symmetry::Transform};
+
+    fn decode(code: &str) -> Pattern {
+        code::decode(code).unwrap().1
+    }
+
+    #[test]
+    fn separates_only_things_that_dont_meet() {
+        let rule: Rule = "B2/S3/@8*".parse().unwrap();
+        let table = rule.compile();
+        let glider = decode("xq2_069z1a6z66");
+        let oscillator = decode("xp3_07z16");
+        let together = |offset: HexCoord| {
+            let mut board = glider.to_board();
+            board.stamp(&oscillator, offset, StampMode::Or);
+            separate(&board, &table, UNSETTLED_WINDOW)
+        };
+
+        // Flying away from it
+        let parts = together(HexCoord::new(0, -20));
+        assert_eq!(parts.len(), 2);
+        let mut codes = parts
+            .iter()
+            .map(|part| classify(part, rule, 100).unwrap().code())
+            .collect::<Vec<_>>();
+        codes.sort_unstable();
+        assert_eq!(codes, ["xp3_07z16", "xq2_069z1a6z66"]);
+
+        // Flying into it
+        assert_eq!(together(HexCoord::new(0, 20)).len(), 1);
+    }
+
+    #[test]
+    fn classifies_known_objects() {
+        let glider_rule: Rule = "B2/S3/@8*".parse().unwrap();
+        let still_rule: Rule = "B2/S23/@8*".parse().unwrap();
+        for (rule, code, class) in [
+            (still_rule, "xs8_0kiz2kiz2", Class::Still),
+            (glider_rule, "xp3_07z16", Class::Oscillator { period: 3 }),
+            (
+                glider_rule,
+                "xq2_069z1a6z66",
+                Class::Spaceship {
+                    period: 2,
+                    displacement: HexCoord::new(0, 1),
+                },
+            ),
+        ] {
+            for symmetry in Transform::symmetries() {
+                let moved = symmetry.then(Transform::translate(HexCoord::new(5, -3)));
+                let object = classify(&decode(code).transformed(moved), rule, 100).unwrap();
+                assert_eq!(object.class, class);
+                assert_eq!(object.code(), code);
+            }
+        }
+    }
+
+    #[test]
+    fn censuses_read_back_the_same() {
+        let config = SoupConfig {
+            region: Region::between(HexCoord::new(0, 0), HexCoord::new(7, 7)),
+            ..SoupConfig::new("B2/S3/@8*".parse().unwrap())
+        };
+        let mut census = Census::new(&config);
+        census.run(&config, 0..10, 2, 3, |_| {});
+        census.run(&config, 20..25, 2, 3, |_| {});
+        assert_eq!(census.soups(), 15);
+        assert!(!census.counts().is_empty());
+
+        let mut written = Vec::new();
+        census.write(&mut written).unwrap();
+        let read = Census::read(written.as_slice()).unwrap();
+        assert_eq!(read, census);
+        assert!(read.matches(&config));
+        assert_eq!(read.remaining(0..30), [10..20, 25..30]);
+    }
+}
@@ -0,0 +1,305 @@
+//! Short names for objects, like apgcodes are for Life.
+//!
+//! A code looks like `xp3_07z16`: what kind of object it is, then an underscore, then the
+//! pattern. `xs` is a still life followed by its population, `xp` is an oscillator
+//! followed by its period, and `xq` is a spaceship followed by its period.
+//!
+//! The pattern gets rotated, reflected and moved to its canonical position first, so
+//! every copy of an object gets the same code. Then it's written out one row (of constant
+//! y) at a time, with one base-27 digit per hex holding its three packed edges. Digits
+//! are `0-9` and `a-q`. Rows are separated by `z`, zeros at the ends of rows are left
+//! off, and runs of zeros inside a row are `w` for two, `x` for three, and `y` followed by
+//! a digit for four or more.
+
+use std::{fmt::Display, str::FromStr};
+
+use crate::{
+    census::{Class, Object},
+    math::{EdgesState, HexCoord},
+    pattern::Pattern,
+    Board,
+};
+
+/// The part of a code before the underscore.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum CodeKind {
+    Still { population: usize },
+    Oscillator { period: u64 },
+    Spaceship { period: u64 },
+}
+
+impl CodeKind {
+    pub fn period(&self) -> u64 {
+        match self {
+            CodeKind::Still { .. } => 1,
+            CodeKind::Oscillator { period } | CodeKind::Spaceship { period } => *period,
+        }
+    }
+}
+
+impl Display for CodeKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CodeKind::Still { population } => write!(f, "xs{}", population),
+            CodeKind::Oscillator { period } => write!(f, "xp{}", period),
+            CodeKind::Spaceship { period } => write!(f, "xq{}", period),
+        }
+    }
+}
+
+impl FromStr for CodeKind {
+    type Err = ParseCodeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let number = |digits: &str| {
+            digits
+                .parse::<u64>()
+                .map_err(|_| ParseCodeError("expected a number after the kind"))
+        };
+        if let Some(population) = s.strip_prefix("xs") {
+            Ok(CodeKind::Still {
+                population: number(population)? as usize,
+            })
+        } else if let Some(period) = s.strip_prefix("xp") {
+            Ok(CodeKind::Oscillator {
+                period: number(period)?,
+            })
+        } else if let Some(period) = s.strip_prefix("xq") {
+            Ok(CodeKind::Spaceship {
+                period: number(period)?,
+            })
+        } else {
+            Err(ParseCodeError("kind should be xs, xp or xq"))
+        }
+    }
+}
+
+/// Something was wrong with an object code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseCodeError(&'static str);
+
+impl Display for ParseCodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "bad object code: {}", self.0)
+    }
+}
+
+impl std::error::Error for ParseCodeError {}
+
+impl Object {
+    pub fn code_kind(&self) -> CodeKind {
+        match self.class {
+            Class::Still => CodeKind::Still {
+                population: self.pattern.population(),
+            },
+            Class::Oscillator { period } => CodeKind::Oscillator { period },
+            Class::Spaceship { period, .. } => CodeKind::Spaceship { period },
+        }
+    }
+
+    /// The object's code. Every orientation and position of it gives the same one, but
+    /// different phases of an oscillator or spaceship don't, so make sure the pattern is
+    /// the phase [`crate::census::classify`] picked.
+    pub fn code(&self) -> String {
+        format!("{}_{}", self.code_kind(), encode(&self.pattern))
+    }
+}
+
+/// Write a pattern out as the part of a code after the underscore.
+///
+/// It gets put in canonical form first, so it doesn't matter how it's turned or where it
+/// is.
+pub fn encode(pattern: &Pattern) -> String {
+    let (canonical, _) = pattern.to_board().canonical();
+    let Some((min, max)) = canonical.bounding_box() else {
+        return "0".to_string();
+    };
+
+    let mut rows = Vec::new();
+    for y in min.y..=max.y {
+        let mut digits = (min.x..=max.x)
+            .map(|x| {
+                canonical
+                    .get_edges(HexCoord::new(x, y))
+                    .unwrap_or_default()
+                    .pack()
+            })
+            .collect::<Vec<_>>();
+        while digits.last() == Some(&0) {
+            digits.pop();
+        }
+
+        let mut row = String::new();
+        let mut zeros = 0;
+        for digit in digits {
+            if digit == 0 {
+                zeros += 1;
+                continue;
+            }
+            push_zeros(&mut row, zeros);
+            zeros = 0;
+            row.push(DIGITS[digit as usize] as char);
+        }
+        rows.push(row);
+    }
+    rows.join("z")
+}
+
+/// Read back the part of a code after the underscore.
+///
+/// The pattern comes out with the corner of its bounding box at the origin, which isn't
+/// necessarily where [`Board::canonical`] would put it.
+pub fn decode_pattern(body: &str) -> Result<Pattern, ParseCodeError> {
+    let mut board = Board::new();
+    let (mut x, mut y) = (0, 0);
+    let mut chars = body.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            'w' => x += 2,
+            'x' => x += 3,
+            'y' => {
+                let count = chars
+                    .next()
+                    .and_then(digit_value)
+                    .ok_or(ParseCodeError("y should be followed by a digit"))?;
+                x += 4 + count as i64;
+            }
+            'z' => {
+                x = 0;
+                y += 1;
+            }
+            c => {
+                let packed = digit_value(c).ok_or(ParseCodeError("unknown character"))?;
+                if packed != 0 {
+                    board.set_edges(HexCoord::new(x, y), EdgesState::unpack(packed));
+                }
+                x += 1;
+            }
+        }
+    }
+    Ok(Pattern::from_board(&board))
+}
+
+/// Read back a whole code.
+///
+/// Spaceship codes don't say which way the spaceship goes; run the pattern with
+/// [`crate::census::classify`] to find out.
+pub fn decode(code: &str) -> Result<(CodeKind, Pattern), ParseCodeError> {
+    let (kind, body) = code
+        .split_once('_')
+        .ok_or(ParseCodeError("expected an underscore"))?;
+    let kind = kind.parse::<CodeKind>()?;
+    let pattern = decode_pattern(body)?;
+    if let CodeKind::Still { population } = kind {
+        if population != pattern.population() {
+            return Err(ParseCodeError("population doesn't match the pattern"));
+        }
+    }
+    Ok((kind, pattern))
+}
+
+const DIGITS: &[u8; 27] = b"0123456789abcdefghijklmnopq";
+
+fn digit_value(c: char) -> Option<u8> {
+    DIGITS
+        .iter()
+        .position(|&digit| digit as char == c)
+        .map(|value| value as u8)
+}
+
+fn push_zeros(row: &mut String, mut zeros: usize) {
+    while zeros > 0 {
+        let run = match zeros {
+            1 => {
+                row.push('0');
+                1
+            }
+            2 => {
+                row.push('w');
+                2
+            }
+            3 => {
+                row.push('x');
+                3
+            }
+            _ => {
+                let extra = (zeros - 4).min(DIGITS.len() - 1);
+                row.push('y');
+                row.push(DIGITS[extra] as char);
+                4 + extra
+            }
+        };
+        zeros -= run;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        region::Region,
+        soup::{Density, SoupSymmetry},
+        symmetry::Transform,
+    };
+
+    /// Soups spread thin enough to have long runs of empty hexes.
+    fn soups() -> impl Iterator<Item = Pattern> {
+        (0..40).map(|seed| {
+            let region = Region::between(HexCoord::new(0, 0), HexCoord::new(30, 4));
+            let density = Density {
+                alive: 0.04 + 0.01 * (seed % 5) as f64,
+                barren: 0.02,
+            };
+            let mut board = Board::new();
+            board.random_fill(&region, density, SoupSymmetry::None, seed);
+            Pattern::from_board(&board)
+        })
+    }
+
+    #[test]
+    fn decoding_gives_back_the_same_thing() {
+        for pattern in soups() {
+            let body = encode(&pattern);
+            let decoded = decode_pattern(&body).unwrap();
+            let (canonical, _) = pattern.canonical();
+            assert_eq!(decoded.normalized().0, canonical.normalized().0, "{body}");
+            assert_eq!(encode(&decoded), body);
+
+            let code = format!("xs{}_{}", pattern.population(), body);
+            let (kind, decoded) = decode(&code).unwrap();
+            assert_eq!(kind.to_string(), format!("xs{}", pattern.population()));
+            assert_eq!(decoded.normalized().0, canonical.normalized().0);
+        }
+    }
+
+    #[test]
+    fn every_orientation_gets_the_same_code() {
+        let mut checked = 0;
+        for pattern in soups() {
+            let code = encode(&pattern);
+            let orientations = Transform::symmetries()
+                .into_iter()
+                .map(|symmetry| pattern.transformed(symmetry).normalized().0)
+                .collect::<Vec<_>>();
+            // Only worth checking if turning it around really does give something different
+            if (1..12).any(|idx| orientations[idx] == orientations[0]) {
+                continue;
+            }
+            for symmetry in Transform::symmetries() {
+                let moved = symmetry.then(Transform::translate(HexCoord::new(-7, 12)));
+                assert_eq!(encode(&pattern.transformed(moved)), code);
+            }
+            checked += 1;
+        }
+        assert!(checked > 0);
+    }
+
+    #[test]
+    fn bad_codes_get_rejected() {
+        assert!(decode("xs3").is_err());
+        assert!(decode("xr3_2o").is_err());
+        assert!(decode("xs4_2o").is_err());
+        assert!(decode("xs3_2!").is_err());
+        assert!(decode("xs3_2o").is_ok());
+    }
+}
@@ -0,0 +1,346 @@
+//! Crashing two patterns into each other every way that fits, and seeing what comes out.
+//!
+//! The first pattern stays put. The second one gets run forward to each of its phases,
+//! aimed at every hex near the first, and backed off until the two are apart and heading
+//! for each other. Each of those gets run until it settles down, what's left gets split
+//! up into objects and classified like a census does, and collisions that make the same
+//! objects get lumped together.
+
+use std::{
+    collections::BTreeMap,
+    io::{self, Write},
+};
+
+use crate::{
+    census,
+    math::{Aliveness, HexCoord},
+    pattern::Pattern,
+    simulation::Simulation,
+    stop::Outcome,
+    transition::TransitionTable,
+    Board, Rule,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CollisionConfig {
+    pub rule: Rule,
+    /// Aim the second pattern at every hex at most this many steps from the middle of the
+    /// first.
+    pub max_offset: i64,
+    /// Give up on the patterns ever meeting, or the collision settling down, after this
+    /// many generations.
+    pub max_generations: u64,
+    /// Give up on collisions that get this many live edges.
+    pub max_population: u64,
+}
+
+impl CollisionConfig {
+    pub fn new(rule: Rule) -> Self {
+        Self {
+            rule,
+            max_offset: 6,
+            max_generations: 1000,
+            max_population: 1000,
+        }
+    }
+}
+
+/// How to set up one collision: run the second pattern `phase` generations, move it
+/// over by `offset`, and put it on the same board as the first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Setup {
+    pub offset: HexCoord,
+    pub phase: u64,
+}
+
+/// What happened in one collision.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CollisionResult {
+    pub setup: Setup,
+    /// Codes of everything left over, sorted. Empty if they wiped each other out.
+    pub products: Vec<String>,
+    /// False if it never settled down, or some piece of it didn't, or it got too big.
+    pub settled: bool,
+    /// When the two patterns first affected each other.
+    pub contact: u64,
+}
+
+/// Every collision that made the same things.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CollisionOutcome {
+    pub products: Vec<String>,
+    pub settled: bool,
+    /// How many setups ended up like this.
+    pub count: usize,
+    /// The first setup that did, by phase and then offset.
+    pub example: Setup,
+}
+
+/// The board a setup starts with.
+pub fn setup_board(a: &Pattern, b: &Pattern, rule: Rule, setup: Setup) -> Board {
+    a.to_board().union(&placed(b, &rule.compile(), setup))
+}
+
+/// The second pattern where a setup puts it.
+fn placed(b: &Pattern, table: &TransitionTable, setup: Setup) -> Board {
+    let mut board = b.to_board();
+    for _ in 0..setup.phase {
+        board.apply_table(table);
+    }
+    Pattern::from_board(&board)
+        .translated(setup.offset)
+        .to_board()
+}
+
+/// Run one collision.
+///
+/// Returns `None` if the patterns don't count as colliding: if they overlap or affect
+/// each other straight away, so they couldn't have come from further apart, or if they
+/// never meet at all.
+pub fn run_collision(
+    a: &Pattern,
+    b: &Pattern,
+    setup: Setup,
+    config: &CollisionConfig,
+) -> Option<CollisionResult> {
+    let table = config.rule.compile();
+    let mut alone_a = a.to_board();
+    let mut alone_b = placed(b, &table, setup);
+    if alone_a
+        .edges()
+        .any(|(pos, _)| alone_b.get_liveness(pos) != Aliveness::Dead)
+    {
+        return None;
+    }
+
+    // Run them together and apart until they stop matching
+    let mut sim = Simulation::new(alone_a.union(&alone_b), config.rule);
+    let contact = loop {
+        if *sim.board() != alone_a.union(&alone_b) {
+            break sim.generation();
+        }
+        if sim.generation() >= config.max_generations {
+            return None;
+        }
+        sim.step();
+        alone_a.apply_table(&table);
+        alone_b.apply_table(&table);
+    };
+    if contact <= 1 {
+        return None;
+    }
+
+    let remaining = config.max_generations.saturating_sub(sim.generation());
+    sim.run_until(remaining, |sim| {
+        sim.outcome().is_settled() || sim.stats().population > config.max_population
+    });
+    if sim.stats().population > config.max_population {
+        return Some(CollisionResult {
+            setup,
+            products: Vec::new(),
+            settled: false,
+            contact,
+        });
+    }
+    let result = census::objects_in(
+        sim.board(),
+        sim.outcome(),
+        config.rule,
+        config.max_generations,
+    );
+    let mut products = result
+        .objects
+        .iter()
+        .map(|object| object.code())
+        .collect::<Vec<_>>();
+    products.sort();
+    Some(CollisionResult {
+        setup,
+        products,
+        settled: result.settled,
+        contact,
+    })
+}
+
+/// Every setup to try: each phase of `b` (as many as its period), aimed at each hex within
+/// `config.max_offset` of the middle of `a`. For each of those, `b` goes back along the way
+/// it moves relative to `a` to the last spot where it's clear of `a`, so the two run into
+/// each other instead of starting on top of each other. Lines that never get near `a` are
+/// left out, and if neither moves relative to the other there's nothing to try at all.
+pub fn setups(a: &Pattern, b: &Pattern, config: &CollisionConfig) -> Vec<Setup> {
+    let table = config.rule.compile();
+    let (Some(a_box), Some((a_period, a_moves))) = (a.bounding_box(), motion(a, config)) else {
+        return Vec::new();
+    };
+    let Some((b_period, b_moves)) = motion(b, config) else {
+        return Vec::new();
+    };
+
+    // How far `b` gets relative to `a` each time they're both back in the same phase
+    let period = lcm(a_period, b_period);
+    let closing =
+        b_moves.scale((period / b_period) as i64) - a_moves.scale((period / a_period) as i64);
+    if closing == HexCoord::new(0, 0) {
+        return Vec::new();
+    }
+
+    let a_middle = middle(a_box);
+    let gap = 2 * table.reach();
+    let mut out = Vec::new();
+    let mut board = b.to_board();
+    for phase in 0..b_period {
+        let Some(b_box) = board.bounding_box() else {
+            break;
+        };
+        let b_middle = middle(b_box);
+        // Far enough along any line through `a` to be well clear of it on either side
+        let span = size(a_box) + size(b_box) + 2 * gap + 2 * config.max_offset + 2;
+        let clear = |offset: HexCoord| apart(a_box, (b_box.0 + offset, b_box.1 + offset), gap);
+        for target in a_middle.range_iter(config.max_offset) {
+            // Start past `a` and back up through it to the last spot before they'd meet
+            let mut offset = target - b_middle + closing.scale(span);
+            let mut met = false;
+            for _ in 0..2 * span {
+                if !clear(offset) {
+                    met = true;
+                } else if met {
+                    out.push(Setup { offset, phase });
+                    break;
+                }
+                offset = offset - closing;
+            }
+        }
+        board.apply_table(&table);
+    }
+    out.sort();
+    out.dedup();
+    out
+}
+
+/// A pattern's period and how far it moves each period, if it settles down.
+fn motion(pattern: &Pattern, config: &CollisionConfig) -> Option<(u64, HexCoord)> {
+    let mut sim = Simulation::new(pattern.to_board(), config.rule);
+    match sim.run_until_settled(config.max_generations) {
+        Outcome::Stable { .. } => Some((1, HexCoord::new(0, 0))),
+        Outcome::Periodic { period, .. } => Some((period, HexCoord::new(0, 0))),
+        Outcome::Moving {
+            period,
+            displacement,
+            ..
+        } => Some((period, displacement)),
+        Outcome::Extinct { .. } | Outcome::Running => None,
+    }
+}
+
+fn middle(bounds: (HexCoord, HexCoord)) -> HexCoord {
+    HexCoord::new((bounds.0.x + bounds.1.x) / 2, (bounds.0.y + bounds.1.y) / 2)
+}
+
+fn size(bounds: (HexCoord, HexCoord)) -> i64 {
+    (bounds.1.x - bounds.0.x).max(bounds.1.y - bounds.0.y)
+}
+
+/// Are two bounding boxes more than `gap` apart along some axis?
+fn apart(a: (HexCoord, HexCoord), b: (HexCoord, HexCoord), gap: i64) -> bool {
+    b.0.x > a.1.x + gap || b.1.x < a.0.x - gap || b.0.y > a.1.y + gap || b.1.y < a.0.y - gap
+}
+
+fn lcm(a: u64, b: u64) -> u64 {
+    let (mut x, mut y) = (a, b);
+    while y != 0 {
+        (x, y) = (y, x % y);
+    }
+    a / x * b
+}
+
+/// Try every setup and lump together the ones with the same products. Outcomes come out
+/// most common first.
+pub fn collide(a: &Pattern, b: &Pattern, config: &CollisionConfig) -> Vec<CollisionOutcome> {
+    let mut outcomes = BTreeMap::<(bool, Vec<String>), CollisionOutcome>::new();
+    for setup in setups(a, b, config) {
+        let Some(result) = run_collision(a, b, setup, config) else {
+            continue;
+        };
+        outcomes
+            .entry((result.settled, result.products.clone()))
+            .and_modify(|outcome| outcome.count += 1)
+            .or_insert(CollisionOutcome {
+                products: result.products,
+                settled: result.settled,
+                count: 1,
+                example: setup,
+            });
+    }
+    let mut out = outcomes.into_values().collect::<Vec<_>>();
+    out.sort_by(|a, b| b.count.cmp(&a.count).then(a.example.cmp(&b.example)));
+    out
+}
+
+/// Write outcomes out, one per line: how many setups gave it, an example setup as
+/// `offset x,y phase p`, and what came out. Products of collisions that didn't settle
+/// are whatever could be figured out.
+pub fn write_report(outcomes: &[CollisionOutcome], mut out: impl Write) -> io::Result<()> {
+    for outcome in outcomes {
+        let mut products = outcome.products.join(" ");
+        if products.is_empty() {
+            products = "nothing".to_string();
+        }
+        if !outcome.settled {
+            products = format!("unsettled: {}", products);
+        }
+        writeln!(
+            out,
+            "{:>6}  offset {},{} phase {}  {}",
+            outcome.count,
+            outcome.example.offset.x,
+            outcome.example.offset.y,
+            outcome.example.phase,
+            products
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::code;
+
+    #[test]
+    fn ships_get_aimed_at_still_lifes() {
+        let config = CollisionConfig {
+            max_generations: 200,
+            max_population: 100,
+            ..CollisionConfig::new("B2/S346/@8*".parse().unwrap())
+        };
+        let glider = code::decode_pattern("069z1a6z66").unwrap();
+        let still = code::decode_pattern("0kikiz2o88iz2iioz286").unwrap();
+
+        let tried = setups(&still, &glider, &config);
+        let hits = tried
+            .iter()
+            .filter_map(|&setup| run_collision(&still, &glider, setup, &config))
+            .collect::<Vec<_>>();
+        // Nearly every line that gets tried actually runs into the still life
+        assert!(
+            hits.len() * 2 > tried.len(),
+            "{} of {}",
+            hits.len(),
+            tried.len()
+        );
+
+        // The glider can take the still life out with it, or turn into a different ship
+        let outcomes = collide(&still, &glider, &config);
+        let products = outcomes
+            .iter()
+            .filter(|outcome| outcome.settled)
+            .map(|outcome| outcome.products.clone())
+            .collect::<Vec<_>>();
+        assert!(products.contains(&vec![]));
+        assert!(products.contains(&vec!["xq4_x9z08bz1a6z66".to_string()]));
+
+        // It works the other way around too, and two still lifes never meet at all
+        assert!(!collide(&glider, &still, &config).is_empty());
+        assert!(setups(&still, &still, &config).is_empty());
+    }
+}
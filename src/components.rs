@@ -0,0 +1,141 @@
+//! Splitting a board up into separate objects.
+
+use ahash::AHashMap;
+
+use crate::{
+    math::{Aliveness, EdgePos, HexCoord},
+    pattern::Pattern,
+    Board, NeighborRegion,
+};
+
+/// What makes two edges part of the same object.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Adjacency {
+    /// One's in the other's neighborhood.
+    Neighbors(NeighborRegion),
+    /// Some hex next to one is at most this many steps from some hex next to the other.
+    ///
+    /// 0 means they have to share a hex.
+    Distance(i64),
+}
+
+/// One separate object on a board.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Component {
+    /// The edges in it, in the same place they were on the board.
+    pub pattern: Pattern,
+    /// Smallest and biggest axial coordinates of the hexes its edges are stored on.
+    pub bounding_box: (HexCoord, HexCoord),
+    /// Number of live edges in it.
+    pub population: usize,
+}
+
+impl Board {
+    /// Group the live edges into objects.
+    ///
+    /// With `include_barren`, barren edges get grouped too; otherwise they're left out
+    /// entirely. The components are sorted by bounding box.
+    pub fn components(&self, adjacency: Adjacency, include_barren: bool) -> Vec<Component> {
+        let edges = self
+            .edges()
+            .filter(|(_, alive)| include_barren || *alive == Aliveness::Alive)
+            .collect::<Vec<_>>();
+        let index = edges
+            .iter()
+            .enumerate()
+            .map(|(idx, (pos, _))| (*pos, idx))
+            .collect::<AHashMap<_, _>>();
+
+        let mut sets = DisjointSets::new(edges.len());
+        match adjacency {
+            Adjacency::Neighbors(region) => {
+                for (idx, (pos, _)) in edges.iter().enumerate() {
+                    for neighbor in region.neighbors(*pos) {
+                        if let Some(&other) = index.get(&neighbor) {
+                            sets.join(idx, other);
+                        }
+                    }
+                }
+            }
+            Adjacency::Distance(distance) => {
+                // Which edges touch each hex
+                let mut touching = AHashMap::<HexCoord, Vec<usize>>::new();
+                for (idx, (pos, _)) in edges.iter().enumerate() {
+                    for hex in sides(*pos) {
+                        touching.entry(hex).or_default().push(idx);
+                    }
+                }
+                for (idx, (pos, _)) in edges.iter().enumerate() {
+                    for hex in sides(*pos) {
+                        for near in hex.range_iter(distance.max(0)) {
+                            for &other in touching.get(&near).into_iter().flatten() {
+                                sets.join(idx, other);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut groups = AHashMap::<usize, Pattern>::new();
+        for (idx, &(pos, alive)) in edges.iter().enumerate() {
+            groups.entry(sets.find(idx)).or_default().set(pos, alive);
+        }
+        let mut out = groups
+            .into_values()
+            .map(|pattern| Component {
+                bounding_box: pattern
+                    .bounding_box()
+                    .expect("components always have something in them"),
+                population: pattern.population(),
+                pattern,
+            })
+            .collect::<Vec<_>>();
+        out.sort_by_key(|component| (component.bounding_box, component.pattern.len()));
+        out
+    }
+}
+
+/// The two hexes on either side of an edge.
+fn sides(pos: EdgePos) -> [HexCoord; 2] {
+    [pos.coord(), pos.coord() + pos.dir()]
+}
+
+/// Union-find, with path halving and union by size.
+pub(crate) struct DisjointSets {
+    parent: Vec<usize>,
+    size: Vec<usize>,
+}
+
+impl DisjointSets {
+    pub(crate) fn new(len: usize) -> Self {
+        Self {
+            parent: (0..len).collect(),
+            size: vec![1; len],
+        }
+    }
+
+    pub(crate) fn find(&mut self, mut idx: usize) -> usize {
+        while self.parent[idx] != idx {
+            self.parent[idx] = self.parent[self.parent[idx]];
+            idx = self.parent[idx];
+        }
+        idx
+    }
+
+    /// Returns whether they weren't already together.
+    pub(crate) fn join(&mut self, a: usize, b: usize) -> bool {
+        let (a, b) = (self.find(a), self.find(b));
+        if a == b {
+            return false;
+        }
+        let (big, small) = if self.size[a] < self.size[b] {
+            (b, a)
+        } else {
+            (a, b)
+        };
+        self.parent[small] = big;
+        self.size[big] += self.size[small];
+        true
+    }
+}
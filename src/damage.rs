@@ -0,0 +1,166 @@
+//! Damage spreading: flip one edge and watch how different things get.
+//!
+//! The original board and the flipped one get run side by side. In an orderly rule the
+//! damage stays small or heals; in a chaotic one it spreads out from where the flip was.
+
+use crate::{
+    math::{Aliveness, EdgePos, RestrictedHexDir},
+    random::Rng,
+    region::Region,
+    Board, Rule,
+};
+
+/// How different the two boards are in one generation.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Damage {
+    /// How many edges are in different states.
+    pub distance: usize,
+    /// Furthest any of those is from the hex the flipped edge is on, in steps. 0 if
+    /// there's no damage.
+    pub radius: i64,
+}
+
+/// The damage in every generation after one flip, starting with the flip itself.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DamageCurve {
+    pub flipped: EdgePos,
+    pub damage: Vec<Damage>,
+}
+
+impl DamageCurve {
+    /// The first generation with no damage at all, if it ever heals.
+    pub fn healed_at(&self) -> Option<u64> {
+        self.damage
+            .iter()
+            .position(|damage| damage.distance == 0)
+            .map(|generation| generation as u64)
+    }
+}
+
+/// What an edge gets flipped to: alive ones die, anything else comes alive.
+pub fn flipped(state: Aliveness) -> Aliveness {
+    match state {
+        Aliveness::Alive => Aliveness::Dead,
+        Aliveness::Dead | Aliveness::Barren => Aliveness::Alive,
+    }
+}
+
+/// Flip `pos` and run both boards for `generations` generations.
+pub fn damage_curve(board: &Board, rule: Rule, pos: EdgePos, generations: u64) -> DamageCurve {
+    let table = rule.compile();
+    let mut original = board.clone();
+    let mut changed = board.clone();
+    changed.set_alive(pos, flipped(board.get_liveness(pos)));
+
+    let mut damage = Vec::with_capacity(generations as usize + 1);
+    for generation in 0..=generations {
+        let diff = original.diff(&changed);
+        if diff.is_empty() {
+            // Same board, same future
+            damage.resize(generations as usize + 1, Damage::default());
+            break;
+        }
+        damage.push(Damage {
+            distance: diff.len(),
+            radius: diff
+                .iter()
+                .map(|change| change.pos.coord().distance(pos.coord()))
+                .max()
+                .unwrap_or(0),
+        });
+        if generation < generations {
+            original.apply_table(&table);
+            changed.apply_table(&table);
+        }
+    }
+    DamageCurve {
+        flipped: pos,
+        damage,
+    }
+}
+
+/// Damage curves averaged over a lot of random flips.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AverageDamage {
+    pub samples: usize,
+    pub distance: Vec<f64>,
+    pub radius: Vec<f64>,
+    /// Fraction of flips that still had some damage.
+    pub surviving: Vec<f64>,
+}
+
+/// Flip `samples` random edges in `region`, one at a time, and average the damage.
+pub fn average_damage(
+    board: &Board,
+    rule: Rule,
+    region: &Region,
+    samples: usize,
+    generations: u64,
+    seed: u64,
+) -> AverageDamage {
+    let mut coords = region.coords();
+    coords.sort_unstable();
+    let mut rng = Rng::new(seed);
+    let len = generations as usize + 1;
+    let mut out = AverageDamage {
+        samples,
+        distance: vec![0.0; len],
+        radius: vec![0.0; len],
+        surviving: vec![0.0; len],
+    };
+    if coords.is_empty() || samples == 0 {
+        return out;
+    }
+
+    for _ in 0..samples {
+        let coord = coords[rng.below(coords.len() as u64) as usize];
+        let edge = RestrictedHexDir::all()[rng.below(3) as usize];
+        let curve = damage_curve(board, rule, EdgePos::new_raw(coord, edge), generations);
+        for (generation, damage) in curve.damage.iter().enumerate() {
+            out.distance[generation] += damage.distance as f64;
+            out.radius[generation] += damage.radius as f64;
+            if damage.distance > 0 {
+                out.surviving[generation] += 1.0;
+            }
+        }
+    }
+    for column in [&mut out.distance, &mut out.radius, &mut out.surviving] {
+        for value in column.iter_mut() {
+            *value /= samples as f64;
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{code, math::HexCoord};
+
+    #[test]
+    fn still_lifes_take_bounded_damage() {
+        let rule: Rule = "B3/S23/@6".parse().unwrap();
+        let board = code::decode_pattern("02oz2k6z22o").unwrap().to_board();
+        let mut healed = 0;
+        for (pos, _) in board.edges() {
+            let curve = damage_curve(&board, rule, pos, 200);
+            assert_eq!(curve.damage.len(), 201);
+            assert!(curve
+                .damage
+                .iter()
+                .all(|damage| damage.distance <= 20 && damage.radius <= 3));
+            healed += curve.healed_at().is_some() as usize;
+        }
+        // Taking out one of them lets it grow back
+        assert_eq!(healed, 1);
+
+        // A lone edge out in the middle of nowhere has nothing to keep it going
+        let curve = damage_curve(
+            &board,
+            rule,
+            EdgePos::new_raw(HexCoord::new(20, 20), RestrictedHexDir::XY),
+            10,
+        );
+        assert_eq!(curve.healed_at(), Some(2));
+    }
+}
@@ -0,0 +1,333 @@
+//! Breeding rules that have lots of spaceships.
+//!
+//! Each round, every rule in the population gets scored by running soups under it (see
+//! [`crate::explore`]) and counting the different kinds of spaceships that come out. The
+//! best few survive, and the rest of the next population are mutated copies of them. The
+//! same seed always gives the same rounds.
+
+use std::io::{self, Write};
+
+use ahash::AHashMap;
+
+use crate::{
+    explore::{self, ExploreConfig, RuleReport},
+    random::Rng,
+    NeighborRegion, Rule,
+};
+
+/// Every neighborhood there is.
+pub const ALL_NEIGHBORHOODS: [NeighborRegion; 5] = [
+    NeighborRegion::Four,
+    NeighborRegion::Six,
+    NeighborRegion::EightCross,
+    NeighborRegion::EightParallel,
+    NeighborRegion::Ten,
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct EvolveConfig {
+    /// How many rules there are each round.
+    pub population: usize,
+    /// How many of the best rules make it to the next round unchanged.
+    pub survivors: usize,
+    /// Neighborhoods rules can have.
+    pub neighborhoods: Vec<NeighborRegion>,
+    /// Chance a mutation switches neighborhoods instead of flipping mask bits.
+    pub neighborhood_change: f64,
+    /// The soups every rule gets scored on.
+    pub soups: ExploreConfig,
+    pub seed: u64,
+}
+
+impl Default for EvolveConfig {
+    fn default() -> Self {
+        Self {
+            population: 24,
+            survivors: 6,
+            neighborhoods: ALL_NEIGHBORHOODS.to_vec(),
+            neighborhood_change: 0.1,
+            soups: ExploreConfig::default(),
+            seed: 0,
+        }
+    }
+}
+
+/// A rule and how it did.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Scored {
+    pub fitness: f64,
+    pub report: RuleReport,
+}
+
+/// Different kinds of spaceships count the most, with more spaceships overall breaking
+/// ties.
+pub fn fitness(report: &RuleReport) -> f64 {
+    let ships = report.spaceships as f64;
+    report.spaceship_kinds as f64 + ships / (ships + 10.0)
+}
+
+/// Change a rule a little: flip one to three bits of its masks, or sometimes move it to
+/// another neighborhood, dropping mask bits it doesn't have room for.
+pub fn mutate(rule: Rule, rng: &mut Rng, config: &EvolveConfig) -> Rule {
+    let others = config
+        .neighborhoods
+        .iter()
+        .copied()
+        .filter(|&neighbors| neighbors != rule.neighbors)
+        .collect::<Vec<_>>();
+    if !others.is_empty() && rng.chance(config.neighborhood_change) {
+        let neighbors = others[rng.below(others.len() as u64) as usize];
+        let keep = (1 << (neighbors.count() + 1)) - 1;
+        return Rule::new_raw(rule.birth_mask & keep, rule.survive_mask & keep, neighbors);
+    }
+
+    let count = rule.neighbors.count() as u64;
+    let (mut birth, mut survive) = (rule.birth_mask, rule.survive_mask);
+    for _ in 0..=rng.below(3) {
+        // Birth bits 1 to count, then survive bits 0 to count; never B0
+        let bit = rng.below(2 * count + 1);
+        if bit < count {
+            birth ^= 1 << (bit + 1);
+        } else {
+            survive ^= 1 << (bit - count);
+        }
+    }
+    Rule::new_raw(birth, survive, rule.neighbors)
+}
+
+/// The whole search.
+pub struct Evolution {
+    config: EvolveConfig,
+    rng: Rng,
+    round: u64,
+    /// The rules that get scored next round.
+    population: Vec<Rule>,
+    scored: Vec<Scored>,
+    /// Rules keep coming back, so don't score them twice.
+    cache: AHashMap<String, RuleReport>,
+}
+
+impl Evolution {
+    /// Start with random rules.
+    pub fn new(config: EvolveConfig) -> Self {
+        assert!(
+            !config.neighborhoods.is_empty(),
+            "need at least one neighborhood"
+        );
+        let mut rng = Rng::new(config.seed);
+        let mut population = Vec::new();
+        while population.len() < config.population.max(1) {
+            let neighbors =
+                config.neighborhoods[rng.below(config.neighborhoods.len() as u64) as usize];
+            let rule = explore::random_rule(neighbors, &mut rng);
+            if !population.contains(&rule) {
+                population.push(rule);
+            }
+        }
+        Self {
+            config,
+            rng,
+            round: 0,
+            population,
+            scored: Vec::new(),
+            cache: AHashMap::new(),
+        }
+    }
+
+    /// Start from these rules instead, filling up with mutants of them if there aren't
+    /// enough.
+    pub fn with_rules(config: EvolveConfig, rules: &[Rule]) -> Self {
+        let mut out = Self::new(config);
+        if !rules.is_empty() {
+            out.population = rules.to_vec();
+            out.fill_with_mutants();
+        }
+        out
+    }
+
+    pub fn config(&self) -> &EvolveConfig {
+        &self.config
+    }
+
+    /// How many rounds have been scored.
+    pub fn round(&self) -> u64 {
+        self.round
+    }
+
+    /// The last round's rules and scores, best first.
+    pub fn scored(&self) -> &[Scored] {
+        &self.scored
+    }
+
+    /// The best rule seen in the last round.
+    pub fn best(&self) -> Option<&Scored> {
+        self.scored.first()
+    }
+
+    /// Score the current rules, then breed the next round from the best of them.
+    pub fn step(&mut self, threads: usize) -> &[Scored] {
+        let unscored = self
+            .population
+            .iter()
+            .copied()
+            .filter(|rule| !self.cache.contains_key(&rule.to_string()))
+            .collect::<Vec<_>>();
+        for report in explore::explore(&unscored, &self.config.soups, threads, |_| {}) {
+            self.cache.insert(report.rule.to_string(), report);
+        }
+
+        let mut scored = self
+            .population
+            .iter()
+            .map(|rule| {
+                let report = self.cache[&rule.to_string()].clone();
+                Scored {
+                    fitness: fitness(&report),
+                    report,
+                }
+            })
+            .collect::<Vec<_>>();
+        // Stable, so ties keep their order and the same seed gives the same result
+        scored.sort_by(|a, b| b.fitness.total_cmp(&a.fitness));
+        self.scored = scored;
+        self.round += 1;
+
+        self.population = self
+            .scored
+            .iter()
+            .take(self.config.survivors.max(1))
+            .map(|scored| scored.report.rule)
+            .collect();
+        self.fill_with_mutants();
+        &self.scored
+    }
+
+    fn fill_with_mutants(&mut self) {
+        let parents = self.population.len();
+        let target = self.config.population.max(parents);
+        let mut attempts = 0;
+        while self.population.len() < target {
+            let parent = self.population[self.rng.below(parents as u64) as usize];
+            let child = mutate(parent, &mut self.rng, &self.config);
+            attempts += 1;
+            // Give up on keeping them all different if it's just not happening
+            if !self.population.contains(&child) || attempts > 100 * target {
+                self.population.push(child);
+            }
+        }
+    }
+
+    /// Write what the search is doing, so a log says how to run it again.
+    pub fn write_header(&self, mut out: impl Write) -> io::Result<()> {
+        let config = &self.config;
+        let neighborhoods = config
+            .neighborhoods
+            .iter()
+            .map(|neighbors| neighbors.to_string())
+            .collect::<Vec<_>>();
+        writeln!(out, "# hexlife evolve")?;
+        writeln!(
+            out,
+            "seed {} population {} survivors {} neighborhoods {} change {}",
+            config.seed,
+            config.population,
+            config.survivors,
+            neighborhoods.join(","),
+            config.neighborhood_change
+        )?;
+        let soups = &config.soups;
+        writeln!(
+            out,
+            "soups {} from {} generations {} max-pop {} density {} {} region {:?}",
+            soups.soups,
+            soups.seed,
+            soups.generations,
+            soups.max_population,
+            soups.density.alive,
+            soups.density.barren,
+            soups.region
+        )
+    }
+
+    /// Write the last round's scores to a log.
+    ///
+    /// Each round is a `round N` line, then one tab-separated line per rule with its
+    /// fitness, the rule, how many spaceships and kinds of spaceship it had, and the most
+    /// common one.
+    pub fn write_round(&self, mut out: impl Write) -> io::Result<()> {
+        writeln!(out, "round {}", self.round)?;
+        for scored in self.scored.iter() {
+            let report = &scored.report;
+            writeln!(
+                out,
+                "{:.3}\t{}\t{}\t{}\t{}",
+                scored.fitness,
+                report.rule,
+                report.spaceships,
+                report.spaceship_kinds,
+                report.common_spaceship.as_deref().unwrap_or("-")
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{math::HexCoord, region::Region};
+
+    fn config(seed: u64) -> EvolveConfig {
+        EvolveConfig {
+            population: 6,
+            survivors: 2,
+            soups: ExploreConfig {
+                region: Region::between(HexCoord::new(0, 0), HexCoord::new(7, 7)),
+                soups: 2,
+                generations: 200,
+                max_population: 500,
+                ..ExploreConfig::default()
+            },
+            seed,
+            ..EvolveConfig::default()
+        }
+    }
+
+    fn rounds(seed: u64, threads: usize) -> Vec<Vec<Scored>> {
+        let mut evolution = Evolution::new(config(seed));
+        (0..3).map(|_| evolution.step(threads).to_vec()).collect()
+    }
+
+    #[test]
+    fn same_seed_same_rounds() {
+        let first = rounds(5, 1);
+        assert_eq!(first, rounds(5, 4));
+        assert_ne!(first, rounds(6, 1));
+
+        // The best rules always make it to the next round
+        let best = first
+            .iter()
+            .map(|scored| scored[0].fitness)
+            .collect::<Vec<_>>();
+        assert!(best.windows(2).all(|pair| pair[1] >= pair[0]), "{best:?}");
+    }
+
+    #[test]
+    fn mutants_never_get_b0() {
+        let config = config(0);
+        let mut rng = Rng::new(1);
+        let mut rule: Rule = "B2/S3/@8*".parse().unwrap();
+        for _ in 0..1000 {
+            rule = mutate(rule, &mut rng, &config);
+            assert_eq!(rule.birth_mask & 1, 0, "{rule}");
+            assert!(
+                rule.birth_mask < 1 << (rule.neighbors.count() + 1),
+                "{rule}"
+            );
+            assert!(
+                rule.survive_mask < 1 << (rule.neighbors.count() + 1),
+                "{rule}"
+            );
+        }
+    }
+}
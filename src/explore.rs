@@ -0,0 +1,356 @@
+//! Surveying lots of rules at once to find the interesting ones.
+//!
+//! Every rule gets the same handful of soups, run for a fixed number of generations, and
+//! gets a [`RuleReport`] of how they went. [`write_table`] puts them all in a
+//! tab-separated table that's easy to sort or load into a spreadsheet.
+
+use std::{
+    collections::BTreeMap,
+    io::{self, Write},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc,
+    },
+};
+
+use crate::{
+    census::{self, Class},
+    math::HexCoord,
+    random::Rng,
+    region::Region,
+    simulation::Simulation,
+    soup::{Density, SoupSymmetry},
+    stop::Outcome,
+    Board, NeighborRegion, Rule,
+};
+
+/// Every rule with this neighborhood, except ones with B0.
+///
+/// B0 rules fill empty space with live edges, so soups in them are all the same
+/// explosion.
+pub fn all_rules(neighbors: NeighborRegion) -> impl Iterator<Item = Rule> {
+    let masks = 1u32 << (neighbors.count() + 1);
+    (0..masks / 2).flat_map(move |birth| {
+        (0..masks).map(move |survive| Rule::new_raw(birth << 1, survive, neighbors))
+    })
+}
+
+/// How many rules [`all_rules`] goes through.
+pub fn rule_count(neighbors: NeighborRegion) -> u64 {
+    1 << (2 * neighbors.count() + 1)
+}
+
+/// Pick one of [`all_rules`] at random.
+pub fn random_rule(neighbors: NeighborRegion, rng: &mut Rng) -> Rule {
+    let masks = 1u64 << (neighbors.count() + 1);
+    let birth = rng.below(masks / 2) as u32;
+    let survive = rng.below(masks) as u32;
+    Rule::new_raw(birth << 1, survive, neighbors)
+}
+
+/// `count` different rules picked at random, or all of them if there aren't that many.
+pub fn sample_rules(neighbors: NeighborRegion, count: usize, seed: u64) -> Vec<Rule> {
+    if count as u64 >= rule_count(neighbors) {
+        return all_rules(neighbors).collect();
+    }
+    let mut rng = Rng::new(seed);
+    let mut picked = BTreeMap::new();
+    while picked.len() < count {
+        let rule = random_rule(neighbors, &mut rng);
+        picked.insert((rule.birth_mask, rule.survive_mask), rule);
+    }
+    picked.into_values().collect()
+}
+
+/// What soups every rule gets run on.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExploreConfig {
+    pub region: Region,
+    pub density: Density,
+    /// Seeds `seed` up to `seed + soups` get used for every rule.
+    pub soups: u64,
+    pub seed: u64,
+    pub generations: u64,
+    /// A soup that gets this many live edges counts as exploding, and stops there.
+    pub max_population: u64,
+}
+
+impl Default for ExploreConfig {
+    fn default() -> Self {
+        Self {
+            region: Region::Parallelogram {
+                corner: HexCoord::new(0, 0),
+                width: 16,
+                height: 16,
+            },
+            density: Density::default(),
+            soups: 8,
+            seed: 0,
+            generations: 500,
+            max_population: 5000,
+        }
+    }
+}
+
+/// How a rule did, averaged over all its soups.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuleReport {
+    pub rule: Rule,
+    /// Live edges at the start, a quarter, half and three quarters of the way through,
+    /// and at the end. Exploded soups count as however big they were when they exploded.
+    pub population: [f64; 5],
+    /// Live edges over all the edges in the bounding box at the end, for soups that
+    /// didn't die out.
+    pub final_density: f64,
+    /// Births and deaths per live edge per generation, over the last quarter of the run,
+    /// or the last quarter before exploding.
+    pub activity: f64,
+    /// Fraction of soups that died out completely.
+    pub died_out: f64,
+    /// Fraction of soups that hit the population limit.
+    pub exploded: f64,
+    /// How many spaceships were left over at the end of all the soups.
+    pub spaceships: u64,
+    /// How many different kinds of spaceships those were, by code.
+    pub spaceship_kinds: u64,
+    /// The code of the most common spaceship.
+    pub common_spaceship: Option<String>,
+}
+
+/// The columns [`write_table`] writes, after the rule.
+pub const COLUMNS: [&str; 11] = [
+    "pop_start",
+    "pop_25",
+    "pop_50",
+    "pop_75",
+    "pop_end",
+    "density",
+    "activity",
+    "died_out",
+    "exploded",
+    "spaceships",
+    "ship_kinds",
+];
+
+impl RuleReport {
+    /// The numbers in the table, in the same order as [`COLUMNS`].
+    pub fn columns(&self) -> [f64; 11] {
+        let [start, quarter, half, three_quarters, end] = self.population;
+        [
+            start,
+            quarter,
+            half,
+            three_quarters,
+            end,
+            self.final_density,
+            self.activity,
+            self.died_out,
+            self.exploded,
+            self.spaceships as f64,
+            self.spaceship_kinds as f64,
+        ]
+    }
+
+    /// The value in the column called `name`.
+    pub fn column(&self, name: &str) -> Option<f64> {
+        let idx = COLUMNS.iter().position(|&column| column == name)?;
+        Some(self.columns()[idx])
+    }
+}
+
+/// Run every soup under one rule.
+pub fn explore_rule(rule: Rule, config: &ExploreConfig) -> RuleReport {
+    let checkpoints = [0, 1, 2, 3, 4].map(|quarter| config.generations * quarter / 4);
+    let soups = config.soups.max(1) as f64;
+    let mut population = [0.0; 5];
+    let (mut density, mut active_soups) = (0.0, 0);
+    let mut activity = 0.0;
+    let (mut died_out, mut exploded) = (0, 0);
+    let mut spaceships = BTreeMap::<String, u64>::new();
+
+    for seed in config.seed..config.seed + config.soups {
+        let mut board = Board::new();
+        board.random_fill(&config.region, config.density, SoupSymmetry::None, seed);
+        let mut sim = Simulation::new(board, rule);
+        let mut sizes = [None; 5];
+        let mut churn = Vec::new();
+        let settled = loop {
+            let generation = sim.generation();
+            let stats = *sim.stats();
+            for (size, &checkpoint) in sizes.iter_mut().zip(checkpoints.iter()) {
+                if generation == checkpoint {
+                    *size = Some(stats.population as f64);
+                }
+            }
+            if generation > 0 {
+                churn.push((stats.births + stats.deaths) as f64 / stats.population.max(1) as f64);
+            }
+            // Nothing's going to change after this
+            let settled = matches!(
+                sim.outcome(),
+                Outcome::Extinct { .. } | Outcome::Stable { .. }
+            );
+            if settled
+                || generation >= config.generations
+                || stats.population >= config.max_population
+            {
+                break settled;
+            }
+            sim.step();
+        };
+
+        let last = sim.stats().population as f64;
+        for (total, size) in population.iter_mut().zip(sizes) {
+            *total += size.unwrap_or(last);
+        }
+        // Soups that settle down stop changing, but ones that explode don't
+        if !settled && !churn.is_empty() {
+            let recent = &churn[churn.len() - churn.len().div_ceil(4)..];
+            activity += recent.iter().sum::<f64>() / recent.len() as f64;
+        }
+        if sim.board().population() == 0 {
+            died_out += 1;
+            continue;
+        }
+        if let Some((min, max)) = sim.board().bounding_box() {
+            let hexes = (max.x - min.x + 1) * (max.y - min.y + 1);
+            density += last / (3 * hexes) as f64;
+            active_soups += 1;
+        }
+        if sim.stats().population >= config.max_population {
+            exploded += 1;
+            continue;
+        }
+
+        let result = census::objects_in(sim.board(), sim.outcome(), rule, config.generations);
+        for object in result.objects {
+            if let Class::Spaceship { .. } = object.class {
+                *spaceships.entry(object.code()).or_default() += 1;
+            }
+        }
+    }
+
+    RuleReport {
+        rule,
+        population: population.map(|total| total / soups),
+        final_density: if active_soups > 0 {
+            density / active_soups as f64
+        } else {
+            0.0
+        },
+        activity: activity / soups,
+        died_out: died_out as f64 / soups,
+        exploded: exploded as f64 / soups,
+        spaceships: spaceships.values().sum(),
+        spaceship_kinds: spaceships.len() as u64,
+        common_spaceship: spaceships
+            .iter()
+            .max_by_key(|(code, count)| (**count, std::cmp::Reverse(*code)))
+            .map(|(code, _)| code.clone()),
+    }
+}
+
+/// Explore a bunch of rules on several threads, calling `progress` as each one finishes.
+/// The reports come back in the same order as the rules.
+pub fn explore(
+    rules: &[Rule],
+    config: &ExploreConfig,
+    threads: usize,
+    mut progress: impl FnMut(&RuleReport),
+) -> Vec<RuleReport> {
+    let next = AtomicUsize::new(0);
+    let mut reports = vec![None; rules.len()];
+    std::thread::scope(|scope| {
+        let (tx, rx) = mpsc::channel();
+        for _ in 0..threads.max(1) {
+            let tx = tx.clone();
+            let next = &next;
+            scope.spawn(move || loop {
+                let idx = next.fetch_add(1, Ordering::Relaxed);
+                let Some(&rule) = rules.get(idx) else {
+                    break;
+                };
+                if tx.send((idx, explore_rule(rule, config))).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(tx);
+        for (idx, report) in rx {
+            progress(&report);
+            reports[idx] = Some(report);
+        }
+    });
+    reports
+        .into_iter()
+        .map(|report| report.expect("every rule got explored"))
+        .collect()
+}
+
+/// Write the reports out as a tab-separated table with a header line.
+pub fn write_table(reports: &[RuleReport], mut out: impl Write) -> io::Result<()> {
+    writeln!(out, "rule\t{}\tcommon_ship", COLUMNS.join("\t"))?;
+    for report in reports {
+        write!(out, "{}", report.rule)?;
+        for value in report.columns() {
+            write!(out, "\t{}", round(value))?;
+        }
+        writeln!(
+            out,
+            "\t{}",
+            report.common_spaceship.as_deref().unwrap_or("-")
+        )?;
+    }
+    Ok(())
+}
+
+/// Keep the table readable; nobody needs 17 digits of activity.
+fn round(value: f64) -> f64 {
+    (value * 1000.0).round() / 1000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> ExploreConfig {
+        ExploreConfig {
+            region: Region::between(HexCoord::new(0, 0), HexCoord::new(7, 7)),
+            soups: 4,
+            generations: 300,
+            max_population: 500,
+            ..ExploreConfig::default()
+        }
+    }
+
+    #[test]
+    fn same_seed_same_reports() {
+        let rules = sample_rules(NeighborRegion::Six, 6, 3);
+        assert_eq!(rules.len(), 6);
+        assert_eq!(rules, sample_rules(NeighborRegion::Six, 6, 3));
+
+        // It doesn't matter how many threads there are or which finishes first
+        let config = config();
+        let reports = explore(&rules, &config, 1, |_| {});
+        assert_eq!(reports, explore(&rules, &config, 3, |_| {}));
+        for (rule, report) in rules.iter().zip(&reports) {
+            assert_eq!(*report, explore_rule(*rule, &config));
+        }
+
+        let other = ExploreConfig {
+            seed: 100,
+            ..config
+        };
+        assert_ne!(reports, explore(&rules, &other, 3, |_| {}));
+    }
+
+    #[test]
+    fn soups_leave_gliders() {
+        let report = explore_rule("B2/S3/@8*".parse().unwrap(), &config());
+        assert_eq!(report.population, [93.5, 7.75, 6.25, 7.0, 6.25]);
+        assert_eq!(report.died_out, 0.25);
+        assert_eq!(report.exploded, 0.0);
+        assert_eq!((report.spaceships, report.spaceship_kinds), (4, 3));
+        assert_eq!(report.common_spaceship.as_deref(), Some("xq2_069z1a6z66"));
+    }
+}
@@ -0,0 +1,327 @@
+//! Telling guns and puffers apart from explosions, for patterns that never settle down.
+//!
+//! A pattern gets run for a while, and its population is checked over the second half of
+//! the run. If the population goes up by the same amount every `period` generations, it's
+//! growing linearly, like a gun or a puffer. If that amount itself goes up by the same
+//! amount every period, it's growing quadratically, like a breeder. If it only ever goes
+//! down, or goes down by the same amount every period, it's decaying. Anything else is
+//! chaotic.
+//!
+//! For linear growth, whatever got added over one period gets split into objects, which
+//! is the stream the pattern is putting out.
+
+use std::collections::BTreeMap;
+
+use crate::{
+    census::{self, classify, Object, UNSETTLED_WINDOW},
+    math::HexCoord,
+    pattern::Pattern,
+    simulation::Simulation,
+    Board, Rule,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Growth {
+    /// The population isn't going anywhere: it settled down, or keeps coming back to the
+    /// same size.
+    Bounded,
+    /// Gains `population` live edges every `period` generations.
+    Linear { period: u64, population: u64 },
+    /// Gains `population` more live edges each `period` generations than it did the
+    /// period before.
+    Quadratic { period: u64, population: u64 },
+    /// Shrinking, so it'll settle down or die out eventually, just not within the run.
+    Decaying,
+    /// Growing, but not regularly.
+    Chaotic,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GrowthConfig {
+    pub rule: Rule,
+    /// How long to run the pattern.
+    pub generations: u64,
+    /// Longest period to look for.
+    pub max_period: u64,
+    /// Stop early once the pattern gets this big.
+    pub max_population: u64,
+    /// Give up on figuring out what an object in the stream is after this many
+    /// generations.
+    pub object_generations: u64,
+}
+
+impl GrowthConfig {
+    pub fn new(rule: Rule) -> Self {
+        Self {
+            rule,
+            generations: 2000,
+            max_period: 256,
+            max_population: 100_000,
+            object_generations: 500,
+        }
+    }
+}
+
+/// What a growing pattern puts out.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Stream {
+    pub period: u64,
+    /// The objects that show up every period, sorted by code.
+    pub objects: Vec<Object>,
+    /// How far whatever's doing the emitting moves each period: nothing for a gun,
+    /// something for a puffer or rake. `None` if there isn't a single thing left over
+    /// that comes back the same each period.
+    pub engine_displacement: Option<HexCoord>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GrowthReport {
+    pub growth: Growth,
+    /// The population every generation it ran for.
+    pub population: Vec<u64>,
+    /// The width and height of the bounding box every generation, in hexes.
+    pub size: Vec<(i64, i64)>,
+    /// Roughly what power of time the population grows like over the second half of the
+    /// run: about 1 for linear growth and 2 for quadratic.
+    pub population_exponent: f64,
+    /// The same for the bigger side of the bounding box.
+    pub size_exponent: f64,
+    /// What it's putting out, for linear growth.
+    pub stream: Option<Stream>,
+}
+
+/// Run a pattern and figure out how it grows.
+pub fn classify_growth(pattern: &Pattern, config: &GrowthConfig) -> GrowthReport {
+    let mut sim = Simulation::new(pattern.to_board(), config.rule);
+    let mut population = Vec::new();
+    let mut size = Vec::new();
+    loop {
+        population.push(sim.stats().population);
+        size.push(match sim.board().bounding_box() {
+            Some((min, max)) => (max.x - min.x + 1, max.y - min.y + 1),
+            None => (0, 0),
+        });
+        if sim.outcome().is_settled()
+            || sim.generation() >= config.generations
+            || sim.stats().population >= config.max_population
+        {
+            break;
+        }
+        sim.step();
+    }
+
+    let start = population.len() / 2;
+    let population_exponent = exponent(&population, start);
+    let sides = size
+        .iter()
+        .map(|&(width, height)| width.max(height) as u64)
+        .collect::<Vec<_>>();
+    let size_exponent = exponent(&sides, start);
+    let mut report = GrowthReport {
+        growth: Growth::Chaotic,
+        population,
+        size,
+        population_exponent,
+        size_exponent,
+        stream: None,
+    };
+
+    if sim.outcome().is_settled() {
+        report.growth = Growth::Bounded;
+        return report;
+    }
+    report.growth = growth(&report.population[start..], config.max_period);
+    if let Growth::Linear { period, .. } = report.growth {
+        report.stream = Some(stream(&mut sim, period, config));
+    }
+    report
+}
+
+/// How a population that hasn't settled down is going.
+fn growth(recent: &[u64], max_period: u64) -> Growth {
+    let max_period = max_period.min(recent.len() as u64 / 3);
+    let differences = |values: &[i64], period: usize| {
+        values
+            .windows(period + 1)
+            .map(|window| window[period] - window[0])
+            .collect::<Vec<_>>()
+    };
+    let recent = recent.iter().map(|&pop| pop as i64).collect::<Vec<_>>();
+
+    // Any multiple of the period works too, so the first one that does is the period
+    for period in 1..=max_period {
+        let gains = differences(&recent, period as usize);
+        if let Some(gain) = constant(&gains) {
+            return match gain {
+                0 => Growth::Bounded,
+                gain if gain > 0 => Growth::Linear {
+                    period,
+                    population: gain as u64,
+                },
+                _ => Growth::Decaying,
+            };
+        }
+        if let Some(gain) = constant(&differences(&gains, period as usize)) {
+            if gain > 0 {
+                return Growth::Quadratic {
+                    period,
+                    population: gain as u64,
+                };
+            }
+        }
+    }
+    if recent.windows(2).all(|pair| pair[1] <= pair[0]) {
+        return Growth::Decaying;
+    }
+    Growth::Chaotic
+}
+
+/// Run one more period and see what came out.
+fn stream(sim: &mut Simulation, period: u64, config: &GrowthConfig) -> Stream {
+    let before = objects(sim.board(), period, config);
+    sim.step_n(period);
+    let after = objects(sim.board(), period, config);
+
+    let mut counts = BTreeMap::<String, (i64, Object)>::new();
+    for object in after.objects {
+        counts.entry(object.code()).or_insert((0, object)).0 += 1;
+    }
+    for object in before.objects {
+        if let Some((count, _)) = counts.get_mut(&object.code()) {
+            *count -= 1;
+        }
+    }
+    let objects = counts
+        .into_values()
+        .flat_map(|(count, object)| std::iter::repeat_n(object, count.max(0) as usize))
+        .collect();
+
+    let engine_displacement = match (before.engine, after.engine) {
+        (Some(before), Some(after)) => {
+            let ((before, from), (after, to)) = (before.normalized(), after.normalized());
+            (before == after).then_some(to - from)
+        }
+        _ => None,
+    };
+    Stream {
+        period,
+        objects,
+        engine_displacement,
+    }
+}
+
+struct Parts {
+    objects: Vec<Object>,
+    /// Everything that didn't turn out to be an object, if that's all one piece.
+    engine: Option<Pattern>,
+}
+
+fn objects(board: &Board, period: u64, config: &GrowthConfig) -> Parts {
+    let table = config.rule.compile();
+    let mut objects = Vec::new();
+    let mut engines = Vec::new();
+    for part in census::separate(board, &table, UNSETTLED_WINDOW.max(period)) {
+        if part.population() == 0 {
+            continue;
+        }
+        match classify(&part, config.rule, config.object_generations) {
+            Some(object) => objects.push(object),
+            None => engines.push(part),
+        }
+    }
+    Parts {
+        objects,
+        engine: (engines.len() == 1).then(|| engines.remove(0)),
+    }
+}
+
+/// The one value everything is, if there is one.
+fn constant(values: &[i64]) -> Option<i64> {
+    let (&first, rest) = values.split_first()?;
+    rest.iter().all(|&value| value == first).then_some(first)
+}
+
+/// Slope of log `values` against log time, from `start` on.
+fn exponent(values: &[u64], start: usize) -> f64 {
+    let points = values
+        .iter()
+        .enumerate()
+        .skip(start.max(1))
+        .filter(|&(_, &value)| value > 0)
+        .map(|(time, &value)| ((time as f64).ln(), (value as f64).ln()))
+        .collect::<Vec<_>>();
+    if points.len() < 2 {
+        return 0.0;
+    }
+    let count = points.len() as f64;
+    let mean_x = points.iter().map(|(x, _)| x).sum::<f64>() / count;
+    let mean_y = points.iter().map(|(_, y)| y).sum::<f64>() / count;
+    let (mut covariance, mut variance) = (0.0, 0.0);
+    for (x, y) in points {
+        covariance += (x - mean_x) * (y - mean_y);
+        variance += (x - mean_x) * (x - mean_x);
+    }
+    if variance == 0.0 {
+        0.0
+    } else {
+        covariance / variance
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::code;
+
+    #[test]
+    fn still_lifes_are_bounded() {
+        let config = GrowthConfig::new("B2/S23/@8*".parse().unwrap());
+        let still = code::decode_pattern("0kiz2kiz2").unwrap();
+        let report = classify_growth(&still, &config);
+        assert_eq!(report.growth, Growth::Bounded);
+        assert!(report.stream.is_none());
+    }
+
+    #[test]
+    fn rakes_grow_linearly() {
+        let config = GrowthConfig {
+            generations: 300,
+            ..GrowthConfig::new("B2/S3/@8*".parse().unwrap())
+        };
+        // Moves three hexes every six generations and leaves a glider behind each time
+        let rake = code::decode_pattern("y1izx2ozw266izx2zw626zwoz286z2").unwrap();
+        let report = classify_growth(&rake, &config);
+        assert_eq!(
+            report.growth,
+            Growth::Linear {
+                period: 6,
+                population: 4
+            }
+        );
+        let stream = report.stream.unwrap();
+        assert_eq!(stream.engine_displacement, Some(HexCoord::new(0, -3)));
+        let codes = stream
+            .objects
+            .iter()
+            .map(|object| object.code())
+            .collect::<Vec<_>>();
+        assert_eq!(codes, ["xq2_069z1a6z66"]);
+    }
+
+    #[test]
+    fn falling_populations_are_decaying() {
+        // Going down by the same amount every other generation, wobbling in between
+        let linear = (0..300)
+            .map(|time| 1000 - 3 * time + [0, 5][time as usize % 2])
+            .collect::<Vec<_>>();
+        assert_eq!(growth(&linear, 256), Growth::Decaying);
+        // Going down less and less
+        let slowing = (0..300).map(|time| 10_000 / (time + 1)).collect::<Vec<_>>();
+        assert_eq!(growth(&slowing, 256), Growth::Decaying);
+        // Going up and down all over the place
+        let wobbling = (0..300_u64)
+            .map(|time| 1000 + time * 7919 % 1009)
+            .collect::<Vec<_>>();
+        assert_eq!(growth(&wobbling, 256), Growth::Chaotic);
+    }
+}
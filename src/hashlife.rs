@@ -0,0 +1,613 @@
+//! Memoized hierarchical evolution, a la HashLife.
+//!
+//! Space is stored as a quadtree over axial coordinates: a node at level `k` covers a
+//! `2^k` by `2^k` parallelogram of hexes, and each leaf is the packed state of the three
+//! edges on one hex. Identical nodes are only stored once, and the result of advancing a
+//! node is cached, so repetitive patterns can be jumped forward enormous numbers of
+//! generations.
+
+use ahash::AHashMap;
+
+use crate::{
+    math::{Aliveness, EdgePos, EdgesState, HexCoord, RestrictedHexDir},
+    transition::TransitionTable,
+    Board, Rule,
+};
+
+/// Once the node table gets this big, it's cleared out between steps.
+const GC_THRESHOLD: usize = 1 << 22;
+
+/// Biggest level the root can get to, so sizes and coordinates still fit in an `i64`.
+const MAX_LEVEL: u8 = 62;
+
+type NodeId = u32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Node {
+    /// Packed `EdgesState` of a single hex.
+    Leaf(u8),
+    /// Children are in the order low-x low-y, high-x low-y, low-x high-y, high-x high-y.
+    Branch { level: u8, children: [NodeId; 4] },
+}
+
+/// A board being evolved by HashLife.
+#[derive(Clone)]
+pub struct HashLife {
+    rule: Rule,
+    table: TransitionTable,
+    /// Log2 of the furthest an edge can see in one generation, rounded up.
+    reach_log: u8,
+
+    nodes: Vec<Node>,
+    /// Number of live edges in each node.
+    populations: Vec<u64>,
+    interned: AHashMap<Node, NodeId>,
+    empties: Vec<NodeId>,
+    /// Maps a node and a log2 step size to its advanced center.
+    results: AHashMap<(NodeId, u8), NodeId>,
+
+    root: NodeId,
+    /// Axial coordinate of the low corner of the root.
+    origin: HexCoord,
+    generation: u64,
+}
+
+impl HashLife {
+    /// Make an empty universe.
+    ///
+    /// Panics on rules with B0, because then empty space doesn't stay empty.
+    pub fn new(rule: Rule) -> Self {
+        let table = rule.compile();
+        assert!(
+            table.is_quiescent(),
+            "hashlife can't evolve rules where empty space gives birth"
+        );
+
+        let reach = table.reach().max(1);
+        let reach_log = (reach as u64).next_power_of_two().trailing_zeros() as u8;
+
+        let mut out = Self {
+            rule,
+            table,
+            reach_log,
+            nodes: Vec::new(),
+            populations: Vec::new(),
+            interned: AHashMap::new(),
+            empties: Vec::new(),
+            results: AHashMap::new(),
+            root: 0,
+            origin: HexCoord::new(0, 0),
+            generation: 0,
+        };
+        out.root = out.empty(out.base_level());
+        out
+    }
+
+    /// Copy the contents of a board into a fresh universe.
+    pub fn from_board(board: &Board, rule: Rule) -> Self {
+        let mut out = Self::new(rule);
+        out.load(board);
+        out
+    }
+
+    /// Write the universe out into a normal sparse board.
+    pub fn to_board(&self) -> Board {
+        let mut board = Board::new();
+        self.write_out(self.root, self.origin, &mut board);
+        board
+    }
+
+    pub fn rule(&self) -> Rule {
+        self.rule
+    }
+
+    /// How many generations have been simulated.
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Number of live edges.
+    pub fn population(&self) -> u64 {
+        self.populations[self.root as usize]
+    }
+
+    /// Number of distinct nodes currently stored, for keeping an eye on memory.
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn get_liveness(&self, pos: EdgePos) -> Aliveness {
+        let rel = pos.coord() - self.origin;
+        let size = 1i64 << self.level(self.root);
+        if rel.x < 0 || rel.y < 0 || rel.x >= size || rel.y >= size {
+            return Aliveness::Dead;
+        }
+        let packed = self.get_cell(self.root, rel.x, rel.y);
+        EdgesState::unpack(packed).get(pos.edge())
+    }
+
+    /// The biggest `log_gens` [`HashLife::step_pow2`] can take.
+    pub fn max_log_gens(&self) -> u8 {
+        MAX_LEVEL - 3 - self.reach_log
+    }
+
+    /// Advance the universe by `2^log_gens` generations.
+    ///
+    /// Panics if `log_gens` is more than [`HashLife::max_log_gens`], or the pattern has
+    /// spread out too far to step that many generations at once, since the universe would
+    /// get too big for `i64` coordinates.
+    pub fn step_pow2(&mut self, log_gens: u8) {
+        let needed =
+            (log_gens as u32 + self.reach_log as u32 + 1).max(self.level(self.root) as u32);
+        assert!(
+            needed + 2 <= MAX_LEVEL as u32,
+            "can't step 2^{} generations at once: the universe would need to be 2^{} hexes \
+             across, and at most 2^{} fits",
+            log_gens,
+            needed + 2,
+            MAX_LEVEL
+        );
+        let generation = self
+            .generation
+            .checked_add(1 << log_gens)
+            .expect("the generation count overflowed");
+        if self.nodes.len() > GC_THRESHOLD {
+            self.collect_garbage();
+        }
+
+        // Make sure the result will be able to hold everything the pattern could possibly
+        // grow into, then put the pattern in the middle quarter so it won't escape.
+        while self.level(self.root) < log_gens + self.reach_log + 1 {
+            self.expand();
+        }
+        self.expand();
+        self.expand();
+
+        let level = self.level(self.root);
+        self.root = self.successor(self.root, log_gens);
+        let quarter = 1i64 << (level - 2);
+        self.origin = self.origin + HexCoord::new(quarter, quarter);
+        self.generation = generation;
+
+        self.shrink();
+    }
+
+    /// Advance the universe by any number of generations.
+    ///
+    /// Panics like [`HashLife::step_pow2`] if `gens` is too big.
+    pub fn step(&mut self, mut gens: u64) {
+        let mut log_gens = 0;
+        while gens != 0 {
+            if gens & 1 != 0 {
+                self.step_pow2(log_gens);
+            }
+            gens >>= 1;
+            log_gens += 1;
+        }
+    }
+
+    /// Throw out every node and cached result that isn't part of the current universe.
+    pub fn collect_garbage(&mut self) {
+        let old_nodes = std::mem::take(&mut self.nodes);
+        self.populations.clear();
+        self.interned.clear();
+        self.empties.clear();
+        self.results.clear();
+
+        let mut remap = AHashMap::new();
+        self.root = self.copy_from(&old_nodes, self.root, &mut remap);
+    }
+
+    fn copy_from(
+        &mut self,
+        old_nodes: &[Node],
+        id: NodeId,
+        remap: &mut AHashMap<NodeId, NodeId>,
+    ) -> NodeId {
+        if let Some(&new) = remap.get(&id) {
+            return new;
+        }
+        let new = match old_nodes[id as usize] {
+            Node::Leaf(packed) => self.leaf(packed),
+            Node::Branch { children, .. } => {
+                let children = children.map(|child| self.copy_from(old_nodes, child, remap));
+                self.branch(children)
+            }
+        };
+        remap.insert(id, new);
+        new
+    }
+
+    fn load(&mut self, board: &Board) {
+        let mut coords = board.cells.keys();
+        let Some(&first) = coords.next() else {
+            return;
+        };
+        let (mut min, mut max) = (first, first);
+        for coord in coords {
+            min = HexCoord::new(min.x.min(coord.x), min.y.min(coord.y));
+            max = HexCoord::new(max.x.max(coord.x), max.y.max(coord.y));
+        }
+
+        let side = ((max.x - min.x).max(max.y - min.y) + 1) as u64;
+        let level = (side.next_power_of_two().trailing_zeros() as u8).max(self.base_level());
+        let mut root = self.empty(level);
+        for (&coord, &packed) in board.cells.iter() {
+            let rel = coord - min;
+            root = self.set_cell(root, rel.x, rel.y, packed);
+        }
+        self.root = root;
+        self.origin = min;
+    }
+
+    fn write_out(&self, id: NodeId, corner: HexCoord, board: &mut Board) {
+        match self.nodes[id as usize] {
+            Node::Leaf(packed) => {
+                if packed != 0 {
+                    board.cells.insert(corner, packed);
+                    board.activity.mark(corner);
+                }
+            }
+            Node::Branch { level, children } => {
+                if self.empties.get(level as usize) == Some(&id) {
+                    return;
+                }
+                let half = 1i64 << (level - 1);
+                for (idx, child) in children.into_iter().enumerate() {
+                    let offset = HexCoord::new(half * (idx as i64 & 1), half * (idx as i64 >> 1));
+                    self.write_out(child, corner + offset, board);
+                }
+            }
+        }
+    }
+
+    /// Smallest level that can be advanced a single generation.
+    fn base_level(&self) -> u8 {
+        self.reach_log + 2
+    }
+
+    fn level(&self, id: NodeId) -> u8 {
+        match self.nodes[id as usize] {
+            Node::Leaf(_) => 0,
+            Node::Branch { level, .. } => level,
+        }
+    }
+
+    fn children(&self, id: NodeId) -> [NodeId; 4] {
+        match self.nodes[id as usize] {
+            Node::Leaf(_) => panic!("leaves don't have children"),
+            Node::Branch { children, .. } => children,
+        }
+    }
+
+    fn intern(&mut self, node: Node, population: u64) -> NodeId {
+        if let Some(&id) = self.interned.get(&node) {
+            return id;
+        }
+        let id = self.nodes.len() as NodeId;
+        self.nodes.push(node);
+        self.populations.push(population);
+        self.interned.insert(node, id);
+        id
+    }
+
+    fn leaf(&mut self, packed: u8) -> NodeId {
+        let state = EdgesState::unpack(packed);
+        let population = RestrictedHexDir::all()
+            .into_iter()
+            .filter(|&edge| state.get(edge) == Aliveness::Alive)
+            .count() as u64;
+        self.intern(Node::Leaf(packed), population)
+    }
+
+    fn branch(&mut self, children: [NodeId; 4]) -> NodeId {
+        let level = self.level(children[0]) + 1;
+        let population = children
+            .iter()
+            .map(|&child| self.populations[child as usize])
+            .sum();
+        self.intern(Node::Branch { level, children }, population)
+    }
+
+    fn empty(&mut self, level: u8) -> NodeId {
+        while self.empties.len() <= level as usize {
+            let id = match self.empties.last() {
+                None => self.leaf(0),
+                Some(&smaller) => self.branch([smaller; 4]),
+            };
+            self.empties.push(id);
+        }
+        self.empties[level as usize]
+    }
+
+    fn is_empty(&mut self, id: NodeId) -> bool {
+        let level = self.level(id);
+        self.empty(level) == id
+    }
+
+    fn get_cell(&self, id: NodeId, x: i64, y: i64) -> u8 {
+        match self.nodes[id as usize] {
+            Node::Leaf(packed) => packed,
+            Node::Branch { level, children } => {
+                let half = 1i64 << (level - 1);
+                let idx = (x >= half) as usize + 2 * (y >= half) as usize;
+                self.get_cell(children[idx], x % half, y % half)
+            }
+        }
+    }
+
+    fn set_cell(&mut self, id: NodeId, x: i64, y: i64, packed: u8) -> NodeId {
+        match self.nodes[id as usize] {
+            Node::Leaf(_) => self.leaf(packed),
+            Node::Branch {
+                level,
+                mut children,
+            } => {
+                let half = 1i64 << (level - 1);
+                let idx = (x >= half) as usize + 2 * (y >= half) as usize;
+                children[idx] = self.set_cell(children[idx], x % half, y % half, packed);
+                self.branch(children)
+            }
+        }
+    }
+
+    /// Grow the root by one level, keeping the contents in the middle.
+    fn expand(&mut self) {
+        let level = self.level(self.root);
+        let e = self.empty(level - 1);
+        let [a, b, c, d] = self.children(self.root);
+        let children = [
+            self.branch([e, e, e, a]),
+            self.branch([e, e, b, e]),
+            self.branch([e, c, e, e]),
+            self.branch([d, e, e, e]),
+        ];
+        self.root = self.branch(children);
+        let quarter = 1i64 << (level - 1);
+        self.origin = self.origin - HexCoord::new(quarter, quarter);
+    }
+
+    /// Shrink the root as long as everything is in its middle.
+    fn shrink(&mut self) {
+        while self.level(self.root) > self.base_level() {
+            let children = self.children(self.root);
+            let grandchildren = children.map(|child| self.children(child));
+            let border_empty = (0..4).all(|idx| {
+                (0..4)
+                    .filter(|&grand| grand != 3 - idx)
+                    .all(|grand| self.is_empty(grandchildren[idx][grand]))
+            });
+            if !border_empty {
+                break;
+            }
+
+            let level = self.level(self.root);
+            self.root = self.centre(self.root);
+            let quarter = 1i64 << (level - 2);
+            self.origin = self.origin + HexCoord::new(quarter, quarter);
+        }
+    }
+
+    /// The node one level down in the middle of this one.
+    fn centre(&mut self, id: NodeId) -> NodeId {
+        let [a, b, c, d] = self.children(id).map(|child| self.children(child));
+        self.branch([a[3], b[2], c[1], d[0]])
+    }
+
+    /// Advance the middle of the node by `2^log_gens` generations.
+    ///
+    /// `log_gens` can be at most `level - 2 - reach_log`, so nothing from outside the node
+    /// can reach the middle in time.
+    fn successor(&mut self, id: NodeId, log_gens: u8) -> NodeId {
+        let level = self.level(id);
+        debug_assert!(log_gens + self.base_level() <= level);
+
+        if self.is_empty(id) {
+            return self.empty(level - 1);
+        }
+        if let Some(&result) = self.results.get(&(id, log_gens)) {
+            return result;
+        }
+
+        let result = if level == self.base_level() {
+            self.base_step(id)
+        } else {
+            let full_speed = log_gens + self.base_level() == level;
+            let sub_log_gens = if full_speed { log_gens - 1 } else { log_gens };
+
+            let [a, b, c, d] = self.children(id).map(|child| self.children(child));
+            #[rustfmt::skip]
+            let grid = [
+                [a[0], a[1], b[0], b[1]],
+                [a[2], a[3], b[2], b[3]],
+                [c[0], c[1], d[0], d[1]],
+                [c[2], c[3], d[2], d[3]],
+            ];
+            let mut advanced = [[0; 3]; 3];
+            for y in 0..3 {
+                for x in 0..3 {
+                    let overlap = self.branch([
+                        grid[y][x],
+                        grid[y][x + 1],
+                        grid[y + 1][x],
+                        grid[y + 1][x + 1],
+                    ]);
+                    advanced[y][x] = self.successor(overlap, sub_log_gens);
+                }
+            }
+
+            let mut quadrants = [0; 4];
+            for (idx, quadrant) in quadrants.iter_mut().enumerate() {
+                let (x, y) = (idx & 1, idx >> 1);
+                *quadrant = if full_speed {
+                    let overlap = self.branch([
+                        advanced[y][x],
+                        advanced[y][x + 1],
+                        advanced[y + 1][x],
+                        advanced[y + 1][x + 1],
+                    ]);
+                    self.successor(overlap, sub_log_gens)
+                } else {
+                    let [a, b, c, d] = [
+                        advanced[y][x],
+                        advanced[y][x + 1],
+                        advanced[y + 1][x],
+                        advanced[y + 1][x + 1],
+                    ]
+                    .map(|node| self.children(node));
+                    self.branch([a[3], b[2], c[1], d[0]])
+                };
+            }
+            self.branch(quadrants)
+        };
+
+        self.results.insert((id, log_gens), result);
+        result
+    }
+
+    /// Advance the middle of a base-level node by one generation the slow way.
+    fn base_step(&mut self, id: NodeId) -> NodeId {
+        let level = self.level(id);
+        let size = 1usize << level;
+        let mut grid = vec![EdgesState::default(); size * size];
+        for y in 0..size {
+            for x in 0..size {
+                grid[x + y * size] = EdgesState::unpack(self.get_cell(id, x as i64, y as i64));
+            }
+        }
+
+        let quarter = size / 4;
+        let half = size / 2;
+        let mut out = vec![0u8; half * half];
+        for y in 0..half {
+            for x in 0..half {
+                let (gx, gy) = (x + quarter, y + quarter);
+                let mut next = EdgesState::default();
+                for edge in RestrictedHexDir::all() {
+                    let pos = EdgePos::new_raw(HexCoord::new(gx as i64, gy as i64), edge);
+                    let config = self.table.configuration(pos, |neighbor| {
+                        let coord = neighbor.coord();
+                        grid[coord.x as usize + coord.y as usize * size].get(neighbor.edge())
+                    });
+                    let here = grid[gx + gy * size].get(edge);
+                    next.set(edge, self.table.next(edge, here, config));
+                }
+                out[x + y * half] = next.pack();
+            }
+        }
+
+        self.build(&out, half, 0, 0, level - 1)
+    }
+
+    fn build(&mut self, grid: &[u8], stride: usize, x: usize, y: usize, level: u8) -> NodeId {
+        if level == 0 {
+            return self.leaf(grid[x + y * stride]);
+        }
+        let half = 1 << (level - 1);
+        let children = [
+            self.build(grid, stride, x, y, level - 1),
+            self.build(grid, stride, x + half, y, level - 1),
+            self.build(grid, stride, x, y + half, level - 1),
+            self.build(grid, stride, x + half, y + half, level - 1),
+        ];
+        self.branch(children)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        code,
+        evolve::ALL_NEIGHBORHOODS,
+        explore::random_rule,
+        pattern::Pattern,
+        random::Rng,
+        region::Region,
+        soup::{Density, SoupSymmetry},
+    };
+
+    #[test]
+    fn matches_stepping_one_at_a_time() {
+        let mut rng = Rng::new(0);
+        let region = Region::Parallelogram {
+            corner: HexCoord::new(-4, -4),
+            width: 8,
+            height: 8,
+        };
+        for neighbors in ALL_NEIGHBORHOODS {
+            for seed in 0..4 {
+                let rule = random_rule(neighbors, &mut rng);
+                let mut board = Board::new();
+                let density = Density {
+                    alive: 0.4,
+                    barren: 0.2,
+                };
+                board.random_fill(&region, density, SoupSymmetry::None, seed);
+                let mut hashlife = HashLife::from_board(&board, rule);
+                assert!(hashlife.to_board() == board);
+                for gens in [1, 3, 5, 7, 13] {
+                    for _ in 0..gens {
+                        board.apply_rule(rule);
+                    }
+                    hashlife.step(gens);
+                    assert!(
+                        hashlife.to_board() == board,
+                        "{} seed {} generation {}",
+                        rule,
+                        seed,
+                        hashlife.generation()
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn follows_spaceships_out_of_the_root() {
+        let rule = "B2/S3/@8*".parse().unwrap();
+        let (_, glider) = code::decode("xq2_069z1a6z66").unwrap();
+        let mut board = glider.to_board();
+        let mut hashlife = HashLife::from_board(&board, rule);
+        let root_level = |hashlife: &HashLife| match hashlife.nodes[hashlife.root as usize] {
+            Node::Leaf(_) => 0,
+            Node::Branch { level, .. } => level,
+        };
+        let start = root_level(&hashlife);
+        for gens in [37, 63, 101] {
+            for _ in 0..gens {
+                board.apply_rule(rule);
+            }
+            hashlife.step(gens);
+            assert!(hashlife.to_board() == board);
+        }
+        assert_eq!(hashlife.population(), board.population() as u64);
+        // The glider's gone 100 hexes, which is well past where the root started out
+        assert!(root_level(&hashlife) > start);
+    }
+
+    #[test]
+    fn biggest_steps_still_fit() {
+        let rule = "B2/S3/@8*".parse().unwrap();
+        let (_, glider) = code::decode("xq2_069z1a6z66").unwrap();
+        let mut hashlife = HashLife::from_board(&glider.to_board(), rule);
+        let log_gens = hashlife.max_log_gens();
+        hashlife.step_pow2(log_gens);
+        assert_eq!(hashlife.generation(), 1 << log_gens);
+        assert_eq!(hashlife.population(), glider.population() as u64);
+        // Way out there, but still in one piece
+        let board = hashlife.to_board();
+        let (min, _) = board.bounding_box().unwrap();
+        assert!(min.y.abs() >= 1 << (log_gens - 2));
+        assert!(Pattern::from_board(&board).canonical().0 == glider.canonical().0);
+    }
+
+    #[test]
+    #[should_panic(expected = "can't step")]
+    fn too_big_steps_panic() {
+        let rule = "B2/S3/@8*".parse().unwrap();
+        let (_, glider) = code::decode("xq2_069z1a6z66").unwrap();
+        let mut hashlife = HashLife::from_board(&glider.to_board(), rule);
+        hashlife.step(u64::MAX);
+    }
+}
@@ -0,0 +1,352 @@
+//! Undoing and redoing steps and edits.
+//!
+//! Every step or edit gets recorded as the list of hexes it changed, with what was stored
+//! there before and after. Going back and forth just means putting one or the other back.
+//! Every so often a whole copy of the board gets saved too, so jumping a long way doesn't
+//! have to go through every change in between.
+
+use std::collections::{BTreeMap, VecDeque};
+
+use ahash::AHashMap;
+
+use crate::{
+    math::{Aliveness, EdgePos, EdgesState, HexCoord},
+    transition::TransitionTable,
+    Board, Rule,
+};
+
+/// How much history to keep around.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HistoryConfig {
+    /// Most steps and edits to remember. The oldest ones get forgotten first.
+    pub max_entries: usize,
+    /// Most changed hexes to remember across all the steps and edits.
+    pub max_changes: usize,
+    /// Save a copy of the whole board after this many steps and edits.
+    pub keyframe_interval: usize,
+}
+
+impl Default for HistoryConfig {
+    fn default() -> Self {
+        Self {
+            max_entries: 10_000,
+            max_changes: 1 << 20,
+            keyframe_interval: 256,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EntryKind {
+    Step,
+    Edit,
+}
+
+#[derive(Debug, Clone)]
+struct Entry {
+    kind: EntryKind,
+    /// Each changed hex, with the packed states before and after.
+    changes: Vec<(HexCoord, u8, u8)>,
+}
+
+/// A board that remembers how it got here.
+pub struct History {
+    board: Board,
+    rule: Rule,
+    table: TransitionTable,
+    config: HistoryConfig,
+    entries: VecDeque<Entry>,
+    /// How many entries have been forgotten off the front.
+    first: usize,
+    /// What generation it was before the first remembered entry.
+    first_generation: u64,
+    /// How many entries the board is past, counting the forgotten ones.
+    cursor: usize,
+    generation: u64,
+    stored_changes: usize,
+    /// Copies of the board at some positions, along with their generation.
+    keyframes: BTreeMap<usize, (u64, Board)>,
+}
+
+impl History {
+    pub fn new(board: Board, rule: Rule) -> Self {
+        Self::with_config(board, rule, HistoryConfig::default())
+    }
+
+    pub fn with_config(board: Board, rule: Rule, config: HistoryConfig) -> Self {
+        let mut keyframes = BTreeMap::new();
+        keyframes.insert(0, (0, board.clone()));
+        Self {
+            board,
+            rule,
+            table: rule.compile(),
+            config,
+            entries: VecDeque::new(),
+            first: 0,
+            first_generation: 0,
+            cursor: 0,
+            generation: 0,
+            stored_changes: 0,
+            keyframes,
+        }
+    }
+
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    pub fn rule(&self) -> Rule {
+        self.rule
+    }
+
+    /// Change the rule for future steps. Stepping back and forth through what's already
+    /// been recorded still replays it the old way.
+    pub fn set_rule(&mut self, rule: Rule) {
+        self.rule = rule;
+        self.table = rule.compile();
+        self.board.wake_all();
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// The earliest generation that can still be gotten back to.
+    pub fn oldest_generation(&self) -> u64 {
+        self.first_generation
+    }
+
+    pub fn can_undo(&self) -> bool {
+        self.cursor > self.first
+    }
+
+    pub fn can_redo(&self) -> bool {
+        self.cursor < self.first + self.entries.len()
+    }
+
+    /// Advance one generation. This throws away anything that could have been redone.
+    pub fn step(&mut self) {
+        let changes = self.board.step_changes(&self.table);
+
+        // Group the changed edges by the hex they're stored on
+        let mut before = AHashMap::<HexCoord, EdgesState>::new();
+        for (pos, old, _) in changes {
+            before
+                .entry(pos.coord())
+                .or_insert_with(|| self.board.get_edges(pos.coord()).unwrap_or_default())
+                .set(pos.edge(), old);
+        }
+        let changes = before
+            .into_iter()
+            .map(|(coord, old)| {
+                let new = self.board.get_edges(coord).unwrap_or_default();
+                (coord, old.pack(), new.pack())
+            })
+            .collect();
+
+        self.generation += 1;
+        self.record(Entry {
+            kind: EntryKind::Step,
+            changes,
+        });
+    }
+
+    pub fn step_n(&mut self, n: u64) {
+        for _ in 0..n {
+            self.step();
+        }
+    }
+
+    /// Set a bunch of edges all at once, as one thing to undo.
+    ///
+    /// This throws away anything that could have been redone, unless nothing actually
+    /// changed.
+    pub fn edit(&mut self, edits: impl IntoIterator<Item = (EdgePos, Aliveness)>) {
+        let mut before = AHashMap::<HexCoord, u8>::new();
+        for (pos, alive) in edits {
+            let coord = pos.coord();
+            let old = self.board.get_edges(coord).unwrap_or_default();
+            before.entry(coord).or_insert_with(|| old.pack());
+            self.board.set_alive(pos, alive);
+        }
+        let changes = before
+            .into_iter()
+            .filter_map(|(coord, old)| {
+                let new = self.board.get_edges(coord).unwrap_or_default().pack();
+                (new != old).then_some((coord, old, new))
+            })
+            .collect::<Vec<_>>();
+        if !changes.is_empty() {
+            self.record(Entry {
+                kind: EntryKind::Edit,
+                changes,
+            });
+        }
+    }
+
+    /// Set one edge, as one thing to undo.
+    pub fn set_alive(&mut self, pos: EdgePos, alive: Aliveness) {
+        self.edit([(pos, alive)]);
+    }
+
+    /// Go back one step or edit. Returns false if there's nothing left to undo.
+    pub fn undo(&mut self) -> bool {
+        if !self.can_undo() {
+            return false;
+        }
+        self.go_back();
+        self.board.wake_all();
+        true
+    }
+
+    /// Redo the last thing undone. Returns false if there's nothing to redo.
+    pub fn redo(&mut self) -> bool {
+        if !self.can_redo() {
+            return false;
+        }
+        self.go_forward();
+        true
+    }
+
+    /// Go to the board as it was at the end of the given generation, after any edits made
+    /// during it.
+    ///
+    /// Going past the end of what's been recorded steps forward as usual. Returns false
+    /// if that generation has been forgotten.
+    pub fn jump_to_generation(&mut self, generation: u64) -> bool {
+        if generation < self.first_generation {
+            return false;
+        }
+
+        // Find the last position at that generation
+        let mut at = self.first_generation;
+        let mut target = None;
+        for (idx, entry) in self.entries.iter().enumerate() {
+            if at == generation {
+                target = Some(self.first + idx);
+            }
+            if entry.kind == EntryKind::Step {
+                at += 1;
+            }
+        }
+        if at == generation {
+            target = Some(self.first + self.entries.len());
+        }
+
+        match target {
+            Some(target) => self.seek(target),
+            None => {
+                // It's in the future
+                self.seek(self.first + self.entries.len());
+                self.step_n(generation - self.generation);
+            }
+        }
+        true
+    }
+
+    /// Move to just after `target` entries, the fastest way possible.
+    fn seek(&mut self, target: usize) {
+        let before = self.keyframes.range(..=target).next_back();
+        let after = self.keyframes.range(target..).next();
+        let nearest = before
+            .into_iter()
+            .chain(after)
+            .min_by_key(|(&pos, _)| pos.abs_diff(target));
+        let mut moved_back = target < self.cursor;
+        if let Some((&pos, (generation, board))) = nearest {
+            if pos.abs_diff(target) < self.cursor.abs_diff(target) {
+                self.board = board.clone();
+                self.generation = *generation;
+                self.cursor = pos;
+                moved_back = true;
+            }
+        }
+
+        while self.cursor < target {
+            self.go_forward();
+        }
+        while self.cursor > target {
+            self.go_back();
+        }
+        if moved_back {
+            self.board.wake_all();
+        }
+    }
+
+    fn go_back(&mut self) {
+        let entry = &self.entries[self.cursor - self.first - 1];
+        for &(coord, old, _) in entry.changes.iter() {
+            self.board.set_edges(coord, EdgesState::unpack(old));
+        }
+        if entry.kind == EntryKind::Step {
+            self.generation -= 1;
+        }
+        self.cursor -= 1;
+    }
+
+    fn go_forward(&mut self) {
+        let entry = &self.entries[self.cursor - self.first];
+        for &(coord, _, new) in entry.changes.iter() {
+            self.board.set_edges(coord, EdgesState::unpack(new));
+        }
+        if entry.kind == EntryKind::Step {
+            self.generation += 1;
+        }
+        self.cursor += 1;
+    }
+
+    fn record(&mut self, entry: Entry) {
+        // Forget about the future
+        for stale in self.entries.drain(self.cursor - self.first..) {
+            self.stored_changes -= stale.changes.len();
+        }
+        self.keyframes.split_off(&(self.cursor + 1));
+
+        self.stored_changes += entry.changes.len();
+        self.entries.push_back(entry);
+        self.cursor += 1;
+        if self
+            .cursor
+            .is_multiple_of(self.config.keyframe_interval.max(1))
+        {
+            self.keyframes
+                .insert(self.cursor, (self.generation, self.board.clone()));
+        }
+
+        // Forget about the distant past
+        while !self.entries.is_empty()
+            && (self.entries.len() > self.config.max_entries
+                || self.stored_changes > self.config.max_changes)
+        {
+            let old = self.entries.pop_front().unwrap();
+            self.stored_changes -= old.changes.len();
+            if old.kind == EntryKind::Step {
+                self.first_generation += 1;
+            }
+            self.first += 1;
+        }
+        self.keyframes = self.keyframes.split_off(&self.first);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{math::RestrictedHexDir, NeighborRegion};
+
+    #[test]
+    fn changing_the_rule_recomputes_everything() {
+        let mut board = Board::new();
+        board.set_alive(
+            EdgePos::new_raw(HexCoord::new(0, 0), RestrictedHexDir::XY),
+            Aliveness::Alive,
+        );
+        let mut history = History::new(board, Rule::new_raw(0, 1, NeighborRegion::Six));
+        history.step_n(3);
+        assert_eq!(history.board().population(), 1);
+
+        history.set_rule(Rule::new_raw(0, 0, NeighborRegion::Six));
+        history.step();
+        assert_eq!(history.board().population(), 0);
+    }
+}
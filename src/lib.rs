@@ -1,20 +1,72 @@
-use std::fmt::Display;
+use std::{fmt::Display, str::FromStr, sync::Arc};
 
-use ahash::AHashMap;
+use activity::{chunk_of, ActivityTracker};
+use ahash::{AHashMap, AHashSet, RandomState};
 use hex2d::Angle;
 use math::{Aliveness, EdgePos, EdgesState, HexCoord, RestrictedHexDir};
+use transition::{TableKey, TransitionTable};
 
+pub mod activity;
+pub mod algebra;
+pub mod census;
+pub mod code;
+pub mod collision;
+pub mod components;
+pub mod damage;
+pub mod evolve;
+pub mod explore;
+pub mod growth;
+pub mod hashlife;
+pub mod history;
 pub mod math;
+pub mod pattern;
+pub mod predecessor;
+pub mod random;
+pub mod region;
+pub mod sat;
+pub mod search;
+pub mod simulation;
+pub mod solver;
+pub mod soup;
+pub mod statistics;
+pub mod stop;
+pub mod symmetry;
+pub mod tracking;
+pub mod transition;
 
-#[derive(Clone)]
+/// Fixed seeds so board hashes are the same every run.
+const HASH_SEEDS: [u64; 4] = [
+    0x6865_786c_6966_6521,
+    0x243f_6a88_85a3_08d3,
+    0x1319_8a2e_0370_7344,
+    0xa409_3822_299f_31d0,
+];
+
+#[derive(Clone, Default)]
 pub struct Board {
     cells: AHashMap<HexCoord, u8>,
+    activity: ActivityTracker,
+    /// The table [`Board::apply_rule`] compiled last, so it doesn't compile it every step.
+    compiled: Option<Arc<TransitionTable>>,
+}
+
+/// Boards are equal if they have the same edges in the same states; which chunks happen to
+/// be active doesn't matter.
+impl PartialEq for Board {
+    fn eq(&self, other: &Self) -> bool {
+        // Hexes with nothing on them never get stored, so this is enough
+        self.cells == other.cells
+    }
 }
 
+impl Eq for Board {}
+
 impl Board {
     pub fn new() -> Self {
         Self {
             cells: AHashMap::new(),
+            activity: ActivityTracker::default(),
+            compiled: None,
         }
     }
 
@@ -32,6 +84,7 @@ impl Board {
     pub fn set_alive(&mut self, pos: EdgePos, alive: Aliveness) {
         match alive {
             Aliveness::Barren | Aliveness::Alive => {
+                self.activity.mark(pos.coord());
                 let here = self.cells.entry(pos.coord()).or_default();
                 let mut state = EdgesState::unpack(*here);
                 state.set(pos.edge(), alive);
@@ -40,6 +93,7 @@ impl Board {
             Aliveness::Dead => {
                 // Don't bother creating and then immediately removing
                 if let Some(here) = self.cells.get_mut(&pos.coord()) {
+                    self.activity.mark(pos.coord());
                     let mut state = EdgesState::unpack(*here);
                     state.set(pos.edge(), alive);
                     let packed = state.pack();
@@ -64,91 +118,206 @@ impl Board {
         self.cells.get(&pos).copied().map(EdgesState::unpack)
     }
 
+    /// Every edge that isn't dead, in no particular order.
+    pub fn edges(&self) -> impl Iterator<Item = (EdgePos, Aliveness)> + '_ {
+        self.cells().flat_map(|(coord, state)| {
+            RestrictedHexDir::all()
+                .into_iter()
+                .map(move |edge| (EdgePos::new_raw(coord, edge), state.get(edge)))
+                .filter(|(_, alive)| *alive != Aliveness::Dead)
+        })
+    }
+
+    /// Every hex with anything going on at it, in no particular order.
+    pub fn cells(&self) -> impl Iterator<Item = (HexCoord, EdgesState)> + '_ {
+        self.cells
+            .iter()
+            .map(|(&coord, &packed)| (coord, EdgesState::unpack(packed)))
+    }
+
+    /// Number of live edges.
+    pub fn population(&self) -> usize {
+        self.count(Aliveness::Alive)
+    }
+
+    /// Number of edges in the given state. Asking about dead edges always gets 0.
+    pub fn count(&self, state: Aliveness) -> usize {
+        self.edges().filter(|(_, alive)| *alive == state).count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    /// Smallest and biggest axial coordinates of the hexes with anything going on at them.
+    pub fn bounding_box(&self) -> Option<(HexCoord, HexCoord)> {
+        let mut coords = self.cells.keys();
+        let first = *coords.next()?;
+        Some(coords.fold((first, first), |(min, max), coord| {
+            (
+                HexCoord::new(min.x.min(coord.x), min.y.min(coord.y)),
+                HexCoord::new(max.x.max(coord.x), max.y.max(coord.y)),
+            )
+        }))
+    }
+
+    /// Hash everything on the board.
+    ///
+    /// Two boards with the same contents get the same hash, no matter how they got there.
+    pub fn state_hash(&self) -> u64 {
+        self.hash_relative_to(HexCoord::new(0, 0))
+    }
+
+    /// Hash the board's contents, ignoring where on the board they are.
+    ///
+    /// Everything is shifted so the smallest coordinate with anything stored at it is at the
+    /// origin. Two boards whose contents are translations of each other get the same hash,
+    /// and the difference between their offsets is how far apart they are.
+    pub fn fingerprint(&self) -> Fingerprint {
+        let offset = self
+            .cells
+            .keys()
+            .min()
+            .copied()
+            .unwrap_or(HexCoord::new(0, 0));
+        Fingerprint {
+            hash: self.hash_relative_to(offset),
+            offset,
+        }
+    }
+
+    /// Everything stored on the board, as `(x, y, packed)`, sorted.
+    ///
+    /// Two boards are equal exactly when these are.
+    pub(crate) fn sorted_cells(&self) -> Vec<(i64, i64, u8)> {
+        let mut out = self
+            .cells
+            .iter()
+            .map(|(coord, &packed)| (coord.x, coord.y, packed))
+            .collect::<Vec<_>>();
+        out.sort_unstable();
+        out
+    }
+
+    fn hash_relative_to(&self, origin: HexCoord) -> u64 {
+        let hasher =
+            RandomState::with_seeds(HASH_SEEDS[0], HASH_SEEDS[1], HASH_SEEDS[2], HASH_SEEDS[3]);
+        // Add them up so the order doesn't matter
+        self.cells
+            .iter()
+            .map(|(coord, packed)| {
+                let rel = *coord - origin;
+                hasher.hash_one((rel.x, rel.y, *packed))
+            })
+            .fold(0u64, |acc, hash| acc.wrapping_add(hash))
+    }
+
+    /// Which parts of the board changed recently.
+    pub fn activity(&self) -> &ActivityTracker {
+        &self.activity
+    }
+
+    /// Step the board forward one generation, compiling the rule only if it's different
+    /// from the last one.
     pub fn apply_rule(&mut self, rule: Rule) {
-        // Maps edge positions to the number of neighbors there.
-        enum Update {
-            NormalNeighborCount(u8),
-            Barren,
+        let table = match &self.compiled {
+            Some(table) if table.key() == TableKey::Rule(rule) => table.clone(),
+            _ => Arc::new(rule.compile()),
+        };
+        self.apply_table(&table);
+        self.compiled = Some(table);
+    }
+
+    /// Step the board forward one generation with a precompiled table.
+    pub fn apply_table(&mut self, table: &TransitionTable) {
+        self.step_changes(table);
+    }
+
+    /// Step the board forward, returning every edge that changed with its old and new states.
+    pub(crate) fn step_changes(
+        &mut self,
+        table: &TransitionTable,
+    ) -> Vec<(EdgePos, Aliveness, Aliveness)> {
+        if self.activity.switch_table(table) {
+            self.wake_all();
         }
-        let mut updates = AHashMap::<EdgePos, Update>::new();
 
+        // Nothing can change unless something near it changed last time.
+        // So only update edges in chunks next to changed ones, and only look for live
+        // edges in chunks that could be neighbors of those.
+        let targets = activity::dilate(&self.activity.begin_step());
+        let sources = activity::dilate(&targets);
+
+        // Anything stored here, or next to something alive, might change.
+        let mut candidates = AHashSet::<EdgePos>::new();
         for (&coord, &packed) in self.cells.iter() {
-            for edge in [
-                RestrictedHexDir::XY,
-                RestrictedHexDir::ZY,
-                RestrictedHexDir::ZX,
-            ] {
-                let state = EdgesState::unpack(packed);
-                let here = EdgePos::new(coord, edge.to_hex2d());
-                let liveness = state.get(here.edge());
-
-                if liveness == Aliveness::Alive {
-                    for neighbor in rule.neighbors.neighbors(here) {
-                        let neighbor_state = self.get_liveness(neighbor);
-                        if neighbor_state != Aliveness::Barren {
-                            match updates.get_mut(&neighbor) {
-                                None => {
-                                    updates.insert(neighbor, Update::NormalNeighborCount(1));
-                                }
-                                Some(Update::NormalNeighborCount(ref mut count)) => {
-                                    *count += 1;
-                                }
-                                Some(Update::Barren) => {
-                                    // This branch shouldn't be taken but I don't think it's bad to
-                                }
-                            }
-                        }
-                    }
+            if !sources.contains(&chunk_of(coord)) {
+                continue;
+            }
+            let state = EdgesState::unpack(packed);
+            for edge in RestrictedHexDir::all() {
+                let here = EdgePos::new_raw(coord, edge);
+                if targets.contains(&chunk_of(coord)) {
+                    candidates.insert(here);
                 }
-
-                if liveness == Aliveness::Alive || liveness == Aliveness::Dead {
-                    if !updates.contains_key(&here) {
-                        updates.insert(here, Update::NormalNeighborCount(0));
+                if state.get(edge) == Aliveness::Alive {
+                    for neighbor in table.neighbors().neighbors(here) {
+                        if targets.contains(&chunk_of(neighbor.coord())) {
+                            candidates.insert(neighbor);
+                        }
                     }
-                } else {
-                    updates.insert(here, Update::Barren);
                 }
             }
         }
 
-        for (edge_pos, update) in updates.into_iter() {
-            match update {
-                Update::NormalNeighborCount(count) => {
-                    let alive = match self.get_liveness(edge_pos) {
-                        Aliveness::Alive => {
-                            if (rule.survive_mask & (1 << count)) != 0 {
-                                Aliveness::Alive
-                            } else {
-                                Aliveness::Barren
-                            }
-                        }
-                        Aliveness::Dead => {
-                            if (rule.birth_mask & (1 << count)) != 0 {
-                                Aliveness::Alive
-                            } else {
-                                Aliveness::Dead
-                            }
-                        }
-                        Aliveness::Barren => {
-                            panic!("should never be trying to update a barren cell normally")
-                        }
-                    };
-                    // println!(
-                    //     "{:?} has {} live neighbors, setting {}",
-                    //     edge_pos, count, should_be_alive
-                    // );
-                    self.set_alive(edge_pos, alive);
-                }
-                Update::Barren => {
-                    self.set_alive(edge_pos, Aliveness::Dead);
-                }
+        let mut changes = Vec::new();
+        for pos in candidates {
+            let here = self.get_liveness(pos);
+            let config = table.configuration(pos, |neighbor| self.get_liveness(neighbor));
+            let next = table.next(pos.edge(), here, config);
+            // Only touch things that actually change, so the tracker stays accurate
+            if next != here {
+                changes.push((pos, here, next));
             }
         }
+        for &(pos, _, alive) in changes.iter() {
+            self.set_alive(pos, alive);
+        }
+        changes
     }
 
     pub fn clear(&mut self) {
         self.cells.clear();
+        self.activity.clear();
+    }
+
+    /// Overwrite all three edges stored on a hex at once.
+    pub(crate) fn set_edges(&mut self, coord: HexCoord, state: EdgesState) {
+        self.activity.mark(coord);
+        let packed = state.pack();
+        if packed == 0 {
+            self.cells.remove(&coord);
+        } else {
+            self.cells.insert(coord, packed);
+        }
     }
+
+    /// Make the next step recompute everything, for when the board got changed in a way
+    /// the tracker can't vouch for.
+    pub(crate) fn wake_all(&mut self) {
+        for &coord in self.cells.keys() {
+            self.activity.mark(coord);
+        }
+    }
+}
+
+/// A translation-independent hash of a board.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Fingerprint {
+    /// Hash of the contents, shifted so `offset` is at the origin.
+    pub hash: u64,
+    /// Smallest coordinate with anything stored at it.
+    pub offset: HexCoord,
 }
 
 /// Instructions on how to update the board.
@@ -175,6 +344,34 @@ impl Rule {
             neighbors,
         }
     }
+
+    /// Compile the rule into a lookup table for stepping.
+    pub fn compile(&self) -> TransitionTable {
+        TransitionTable::from_rule(*self)
+    }
+
+    /// What an edge in the given state becomes with the given number of live neighbors.
+    ///
+    /// Barren edges always go back to being dead.
+    fn transition(&self, here: Aliveness, count: u8) -> Aliveness {
+        match here {
+            Aliveness::Alive => {
+                if (self.survive_mask & (1 << count)) != 0 {
+                    Aliveness::Alive
+                } else {
+                    Aliveness::Barren
+                }
+            }
+            Aliveness::Dead => {
+                if (self.birth_mask & (1 << count)) != 0 {
+                    Aliveness::Alive
+                } else {
+                    Aliveness::Dead
+                }
+            }
+            Aliveness::Barren => Aliveness::Dead,
+        }
+    }
 }
 
 impl Display for Rule {
@@ -196,6 +393,59 @@ impl Display for Rule {
     }
 }
 
+impl FromStr for Rule {
+    type Err = ParseRuleError;
+
+    /// Read a rule back out of the same format it gets displayed in, like `B2/S34/@6`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.trim().split('/');
+        let (Some(birth), Some(survive), Some(neighbors), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Err(ParseRuleError("expected B.../S.../@..."));
+        };
+
+        let neighbors = neighbors
+            .strip_prefix('@')
+            .ok_or(ParseRuleError("neighbors should start with @"))?
+            .parse::<NeighborRegion>()?;
+        let mask = |part: &str, prefix: char| {
+            let digits = part
+                .strip_prefix(prefix)
+                .or_else(|| part.strip_prefix(prefix.to_ascii_lowercase()))
+                .ok_or(ParseRuleError(
+                    "birth and survival should start with B and S",
+                ))?;
+            let mut mask = 0u32;
+            for digit in digits.chars() {
+                let count = digit
+                    .to_digit(16)
+                    .filter(|&count| count <= neighbors.count())
+                    .ok_or(ParseRuleError("neighbor count out of range"))?;
+                mask |= 1 << count;
+            }
+            Ok(mask)
+        };
+        Ok(Rule::new_raw(
+            mask(birth, 'B')?,
+            mask(survive, 'S')?,
+            neighbors,
+        ))
+    }
+}
+
+/// Something was wrong with a rule string.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ParseRuleError(&'static str);
+
+impl Display for ParseRuleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "bad rule: {}", self.0)
+    }
+}
+
+impl std::error::Error for ParseRuleError {}
+
 /// What is considered to be a neighbor?
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum NeighborRegion {
@@ -286,3 +536,18 @@ impl Display for NeighborRegion {
         }
     }
 }
+
+impl FromStr for NeighborRegion {
+    type Err = ParseRuleError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "4" => Ok(NeighborRegion::Four),
+            "6" => Ok(NeighborRegion::Six),
+            "8*" => Ok(NeighborRegion::EightCross),
+            "8=" => Ok(NeighborRegion::EightParallel),
+            "10" => Ok(NeighborRegion::Ten),
+            _ => Err(ParseRuleError("unknown neighbor region")),
+        }
+    }
+}
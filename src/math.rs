@@ -53,6 +53,15 @@ pub enum RestrictedHexDir {
 }
 
 impl RestrictedHexDir {
+    /// All three tracked directions, in the order they're packed.
+    pub fn all() -> [RestrictedHexDir; 3] {
+        [
+            RestrictedHexDir::XY,
+            RestrictedHexDir::ZY,
+            RestrictedHexDir::ZX,
+        ]
+    }
+
     pub fn to_hex2d(&self) -> Direction {
         match self {
             RestrictedHexDir::XY => Direction::XY,
@@ -141,7 +150,7 @@ impl EdgesState {
     }
 
     pub(crate) fn unpack(packed: u8) -> Self {
-        let xy = (packed / 1) % 3;
+        let xy = packed % 3;
         let zy = (packed / 3) % 3;
         let zx = (packed / 9) % 3;
         Self {
@@ -0,0 +1,220 @@
+//! Patterns that aren't on any particular board.
+
+use ahash::AHashMap;
+
+use crate::{
+    math::{Aliveness, EdgePos, HexCoord, RestrictedHexDir},
+    region::Region,
+    symmetry::Transform,
+    Board,
+};
+
+/// A bunch of edges and what state they're in.
+///
+/// Anything not in the pattern is dead.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Pattern {
+    /// Never has any dead edges in it.
+    edges: AHashMap<EdgePos, Aliveness>,
+}
+
+/// How to combine a pattern with what's already on the board.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StampMode {
+    /// Every hex in the pattern's bounding box gets replaced with the pattern, dead edges
+    /// and all.
+    Overwrite,
+    /// Edges that are alive or barren in the pattern get set; everything else is left alone.
+    Or,
+    /// Edges that are alive or barren in exactly one of the board and the pattern end up in
+    /// that state; edges that are in both end up dead.
+    Xor,
+    /// Like `Or`, but only if every edge it would set is dead on the board. Otherwise
+    /// nothing happens.
+    OnlyIfEmpty,
+}
+
+impl Pattern {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_board(board: &Board) -> Self {
+        board.edges().collect()
+    }
+
+    pub fn to_board(&self) -> Board {
+        let mut board = Board::new();
+        for (&pos, &alive) in self.edges.iter() {
+            board.set_alive(pos, alive);
+        }
+        board
+    }
+
+    pub fn get(&self, pos: EdgePos) -> Aliveness {
+        self.edges.get(&pos).copied().unwrap_or_default()
+    }
+
+    pub fn set(&mut self, pos: EdgePos, alive: Aliveness) {
+        if alive == Aliveness::Dead {
+            self.edges.remove(&pos);
+        } else {
+            self.edges.insert(pos, alive);
+        }
+    }
+
+    /// Every edge that isn't dead, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = (EdgePos, Aliveness)> + '_ {
+        self.edges.iter().map(|(&pos, &alive)| (pos, alive))
+    }
+
+    /// Number of edges that aren't dead.
+    pub fn len(&self) -> usize {
+        self.edges.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.edges.is_empty()
+    }
+
+    /// Number of live edges.
+    pub fn population(&self) -> usize {
+        self.count(Aliveness::Alive)
+    }
+
+    /// Number of edges in the given state. Asking about dead edges always gets 0.
+    pub fn count(&self, state: Aliveness) -> usize {
+        self.edges.values().filter(|&&alive| alive == state).count()
+    }
+
+    /// Smallest and biggest axial coordinates of the hexes the edges are stored on.
+    pub fn bounding_box(&self) -> Option<(HexCoord, HexCoord)> {
+        let mut coords = self.edges.keys().map(|pos| pos.coord());
+        let first = coords.next()?;
+        Some(coords.fold((first, first), |(min, max), coord| {
+            (
+                HexCoord::new(min.x.min(coord.x), min.y.min(coord.y)),
+                HexCoord::new(max.x.max(coord.x), max.y.max(coord.y)),
+            )
+        }))
+    }
+
+    pub fn transformed(&self, transform: Transform) -> Pattern {
+        Pattern {
+            edges: self
+                .edges
+                .iter()
+                .map(|(&pos, &alive)| (transform.apply_edge(pos), alive))
+                .collect(),
+        }
+    }
+
+    pub fn translated(&self, by: HexCoord) -> Pattern {
+        self.transformed(Transform::translate(by))
+    }
+
+    /// Move the pattern so the smallest coordinate with anything on it is at the origin.
+    ///
+    /// Returns the moved pattern and where that coordinate used to be.
+    pub fn normalized(&self) -> (Pattern, HexCoord) {
+        let offset = self
+            .edges
+            .keys()
+            .map(|pos| pos.coord())
+            .min()
+            .unwrap_or(HexCoord::new(0, 0));
+        (self.translated(-offset), offset)
+    }
+
+    /// Get the same pattern for every rotation, reflection and translation of this one.
+    ///
+    /// Returns the canonical pattern and the transform that turns this pattern into it.
+    pub fn canonical(&self) -> (Pattern, Transform) {
+        let (board, transform) = self.to_board().canonical();
+        (Pattern::from_board(&board), transform)
+    }
+}
+
+impl FromIterator<(EdgePos, Aliveness)> for Pattern {
+    fn from_iter<T: IntoIterator<Item = (EdgePos, Aliveness)>>(iter: T) -> Self {
+        let mut out = Pattern::new();
+        for (pos, alive) in iter {
+            out.set(pos, alive);
+        }
+        out
+    }
+}
+
+impl Board {
+    /// Put a pattern onto the board, moved over by `offset`.
+    ///
+    /// Returns whether anything was stamped, which is only ever false for
+    /// [`StampMode::OnlyIfEmpty`].
+    pub fn stamp(&mut self, pattern: &Pattern, offset: HexCoord, mode: StampMode) -> bool {
+        match mode {
+            StampMode::Overwrite => {
+                if let Some((min, max)) = pattern.bounding_box() {
+                    for coord in Region::between(min + offset, max + offset).coords() {
+                        for edge in RestrictedHexDir::all() {
+                            self.set_alive(EdgePos::new_raw(coord, edge), Aliveness::Dead);
+                        }
+                    }
+                }
+                for (pos, alive) in pattern.iter() {
+                    self.set_alive(EdgePos::new_raw(pos.coord() + offset, pos.edge()), alive);
+                }
+            }
+            StampMode::Or => {
+                for (pos, alive) in pattern.iter() {
+                    self.set_alive(EdgePos::new_raw(pos.coord() + offset, pos.edge()), alive);
+                }
+            }
+            StampMode::Xor => {
+                for (pos, alive) in pattern.iter() {
+                    let pos = EdgePos::new_raw(pos.coord() + offset, pos.edge());
+                    let next = if self.get_liveness(pos) == Aliveness::Dead {
+                        alive
+                    } else {
+                        Aliveness::Dead
+                    };
+                    self.set_alive(pos, next);
+                }
+            }
+            StampMode::OnlyIfEmpty => {
+                let blocked = pattern.iter().any(|(pos, _)| {
+                    let pos = EdgePos::new_raw(pos.coord() + offset, pos.edge());
+                    self.get_liveness(pos) != Aliveness::Dead
+                });
+                if blocked {
+                    return false;
+                }
+                for (pos, alive) in pattern.iter() {
+                    self.set_alive(EdgePos::new_raw(pos.coord() + offset, pos.edge()), alive);
+                }
+            }
+        }
+        true
+    }
+
+    /// Copy out every edge stored on a hex in the region.
+    pub fn extract(&self, region: &Region) -> Pattern {
+        let mut out = Pattern::new();
+        // Look at whichever of the two is smaller
+        if region.len() < self.cells.len() {
+            for coord in region.coords() {
+                if let Some(state) = self.get_edges(coord) {
+                    for edge in RestrictedHexDir::all() {
+                        out.set(EdgePos::new_raw(coord, edge), state.get(edge));
+                    }
+                }
+            }
+        } else {
+            for (pos, alive) in self.edges() {
+                if region.contains(pos.coord()) {
+                    out.set(pos, alive);
+                }
+            }
+        }
+        out
+    }
+}
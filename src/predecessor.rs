@@ -0,0 +1,168 @@
+//! Finding what a pattern could have come from.
+//!
+//! A pattern with no parent at all is a Garden of Eden. Live edges never go straight to
+//! dead, they either survive or turn barren, so every live edge of a parent has to be
+//! somewhere the target has something. Barren edges in a parent only matter where they
+//! stop something being born, which has to be near those live edges. So a parent never
+//! needs anything further out than the rule can see from the target, which makes the
+//! search exact. (Rules with B0 are the exception, but they can't have finite parents
+//! anyway.)
+
+use ahash::AHashSet;
+
+use crate::{
+    math::{Aliveness, HexCoord},
+    region::Region,
+    sat::Encoding,
+    solver::Outcome,
+    Board, Rule,
+};
+
+/// The target's bounding box, grown by how far the rule can see, which is always enough;
+/// see the module docs.
+pub fn default_region(rule: Rule, target: &Board) -> Region {
+    let reach = rule.compile().reach();
+    match target.bounding_box() {
+        Some((min, max)) => Region::between(
+            min - HexCoord::new(reach, reach),
+            max + HexCoord::new(reach, reach),
+        ),
+        None => Region::Coords(AHashSet::new()),
+    }
+}
+
+/// Does `parent` turn into exactly `target` after one generation?
+pub fn is_predecessor(rule: Rule, parent: &Board, target: &Board) -> bool {
+    let mut next = parent.clone();
+    next.apply_rule(rule);
+    next == *target
+}
+
+/// Find a parent of `target` that fits in `region`, or `None` if there isn't one.
+///
+/// Use [`default_region`] to search everywhere a parent could be, or something smaller to
+/// only look for parents there. Any hexes of the target outside the region get added to
+/// it. Parents only have barren edges where they're needed to stop something being
+/// born.
+pub fn find_predecessor(rule: Rule, target: &Board, region: &Region) -> Option<Board> {
+    find_predecessors(rule, target, region, 1).pop()
+}
+
+/// Whether `target` has no parent that fits in `region`.
+pub fn is_garden_of_eden(rule: Rule, target: &Board, region: &Region) -> bool {
+    find_predecessor(rule, target, region).is_none()
+}
+
+/// Find up to `max` different parents of `target` that fit in `region`.
+pub fn find_predecessors(rule: Rule, target: &Board, region: &Region, max: usize) -> Vec<Board> {
+    let region = covering(region, target);
+    let mut encoding = Encoding::new(rule, &region, 1);
+    encoding.fix_board(1, target);
+    let edges = encoding.edges().to_vec();
+
+    let mut solver = encoding.cnf().solver();
+    let mut out = Vec::new();
+    while out.len() < max {
+        let Outcome::Sat(model) = solver.solve() else {
+            break;
+        };
+        let parent = without_extra_barren(rule, encoding.decode(&model, 0), target);
+        debug_assert!(is_predecessor(rule, &parent, target));
+        // Rule out these live edges for next time, whatever the barren ones are
+        let clause = edges
+            .iter()
+            .filter_map(|&pos| encoding.alive_var(0, pos))
+            .map(|var| if model[var as usize - 1] { -var } else { var })
+            .collect::<Vec<_>>();
+        solver.add_clause(&clause);
+        out.push(parent);
+    }
+    out
+}
+
+/// `region` plus any hexes of `target` that aren't in it.
+fn covering(region: &Region, target: &Board) -> Region {
+    let missing = target
+        .cells()
+        .map(|(coord, _)| coord)
+        .filter(|&coord| !region.contains(coord))
+        .collect::<Vec<_>>();
+    if missing.is_empty() {
+        return region.clone();
+    }
+    Region::Coords(region.coords().into_iter().chain(missing).collect())
+}
+
+/// Drop barren edges from a parent that it still works without.
+fn without_extra_barren(rule: Rule, mut parent: Board, target: &Board) -> Board {
+    let barren = parent
+        .edges()
+        .filter(|(_, state)| *state == Aliveness::Barren)
+        .map(|(pos, _)| pos)
+        .collect::<Vec<_>>();
+    for pos in barren {
+        parent.set_alive(pos, Aliveness::Dead);
+        if !is_predecessor(rule, &parent, target) {
+            parent.set_alive(pos, Aliveness::Barren);
+        }
+    }
+    parent
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        math::{EdgePos, RestrictedHexDir},
+        soup::{Density, SoupSymmetry},
+    };
+
+    #[test]
+    fn stepped_soups_have_parents() {
+        for (seed, rule) in ["B2/S3/@8*", "B24/S23/@6", "B3/S12/@4", "B2/S34/@10"]
+            .into_iter()
+            .enumerate()
+        {
+            let rule: Rule = rule.parse().unwrap();
+            let mut target = Board::new();
+            let region = Region::between(HexCoord::new(0, 0), HexCoord::new(3, 3));
+            target.random_fill(
+                &region,
+                Density::alive(0.4),
+                SoupSymmetry::None,
+                seed as u64,
+            );
+            target.apply_rule(rule);
+
+            let parents = find_predecessors(rule, &target, &default_region(rule, &target), 3);
+            assert!(!parents.is_empty(), "{rule}");
+            for parent in parents.iter() {
+                let mut next = parent.clone();
+                next.apply_rule(rule);
+                assert!(next == target, "{rule}");
+            }
+            for (idx, parent) in parents.iter().enumerate() {
+                assert!(parents[..idx].iter().all(|other| other != parent));
+            }
+        }
+    }
+
+    #[test]
+    fn barren_edges_need_something_to_die() {
+        // Everything survives, so nothing can ever go barren
+        let rule: Rule = "B2/S0123456/@6".parse().unwrap();
+        let mut target = Board::new();
+        target.set_alive(
+            EdgePos::new_raw(HexCoord::new(0, 0), RestrictedHexDir::XY),
+            Aliveness::Barren,
+        );
+        let region = default_region(rule, &target);
+        assert!(find_predecessor(rule, &target, &region).is_none());
+        assert!(is_garden_of_eden(rule, &target, &region));
+
+        // But it can if nothing survives alone
+        let rule: Rule = "B2/S123456/@6".parse().unwrap();
+        let parent = find_predecessor(rule, &target, &default_region(rule, &target)).unwrap();
+        assert!(is_predecessor(rule, &parent, &target));
+    }
+}
@@ -0,0 +1,111 @@
+//! Areas of the board.
+
+use ahash::AHashSet;
+
+use crate::math::HexCoord;
+
+/// A set of hexes. Each hex owns the three edges stored on it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Region {
+    /// Every hex at most `radius` steps away from `center`.
+    Hexagon { center: HexCoord, radius: i64 },
+    /// Every hex from `corner` up to `corner + (width - 1, height - 1)` in axial coordinates.
+    Parallelogram {
+        corner: HexCoord,
+        width: i64,
+        height: i64,
+    },
+    /// Exactly these hexes.
+    Coords(AHashSet<HexCoord>),
+}
+
+impl Region {
+    /// The smallest parallelogram containing both corners.
+    pub fn between(a: HexCoord, b: HexCoord) -> Self {
+        Region::Parallelogram {
+            corner: HexCoord::new(a.x.min(b.x), a.y.min(b.y)),
+            width: (a.x - b.x).abs() + 1,
+            height: (a.y - b.y).abs() + 1,
+        }
+    }
+
+    pub fn contains(&self, coord: HexCoord) -> bool {
+        match self {
+            Region::Hexagon { center, radius } => center.distance(coord) <= *radius,
+            Region::Parallelogram {
+                corner,
+                width,
+                height,
+            } => {
+                let rel = coord - *corner;
+                (0..*width).contains(&rel.x) && (0..*height).contains(&rel.y)
+            }
+            Region::Coords(coords) => coords.contains(&coord),
+        }
+    }
+
+    /// Every hex in the region, in no particular order.
+    pub fn coords(&self) -> Vec<HexCoord> {
+        match self {
+            Region::Hexagon { radius, .. } if *radius < 0 => Vec::new(),
+            Region::Hexagon { center, radius } => center.range_iter(*radius).collect(),
+            Region::Parallelogram {
+                corner,
+                width,
+                height,
+            } => (0..*height)
+                .flat_map(|y| (0..*width).map(move |x| *corner + HexCoord::new(x, y)))
+                .collect(),
+            Region::Coords(coords) => coords.iter().copied().collect(),
+        }
+    }
+
+    /// How many hexes are in the region.
+    pub fn len(&self) -> usize {
+        match self {
+            Region::Hexagon { radius, .. } => {
+                if *radius < 0 {
+                    0
+                } else {
+                    (3 * radius * (radius + 1) + 1) as usize
+                }
+            }
+            Region::Parallelogram { width, height, .. } => {
+                (*width).max(0) as usize * (*height).max(0) as usize
+            }
+            Region::Coords(coords) => coords.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The hex closest to the middle of the region, give or take rounding.
+    pub fn center(&self) -> HexCoord {
+        match self {
+            Region::Hexagon { center, .. } => *center,
+            Region::Parallelogram {
+                corner,
+                width,
+                height,
+            } => *corner + HexCoord::new((width - 1).div_euclid(2), (height - 1).div_euclid(2)),
+            Region::Coords(coords) => {
+                if coords.is_empty() {
+                    return HexCoord::new(0, 0);
+                }
+                let n = coords.len() as f64;
+                let (x, y) = coords.iter().fold((0.0, 0.0), |(x, y), coord| {
+                    (x + coord.x as f64, y + coord.y as f64)
+                });
+                HexCoord::new((x / n).round() as i64, (y / n).round() as i64)
+            }
+        }
+    }
+}
+
+impl FromIterator<HexCoord> for Region {
+    fn from_iter<T: IntoIterator<Item = HexCoord>>(iter: T) -> Self {
+        Region::Coords(iter.into_iter().collect())
+    }
+}
@@ -0,0 +1,552 @@
+//! Turning searches into SAT problems.
+//!
+//! An [`Encoding`] says that a region goes through a few generations of a rule, with
+//! everything outside the region staying dead. Constrain some of the generations (a
+//! spaceship has to come back shifted over, a predecessor has to turn into a given board),
+//! then solve it with the built-in [`Solver`] or write it out as DIMACS for a real solver,
+//! and [`Encoding::decode`] the answer.
+//!
+//! Every edge in every generation gets two variables, one for being alive and one for
+//! being barren; dead is neither.
+
+use std::{
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    process::{Command, Stdio},
+};
+
+use ahash::AHashMap;
+
+use crate::{
+    math::{Aliveness, EdgePos, HexCoord, RestrictedHexDir},
+    region::Region,
+    solver::{Outcome, Solver},
+    Board, Rule,
+};
+
+/// A formula in conjunctive normal form, with DIMACS-style literals: variables start at 1
+/// and negative means negated.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Cnf {
+    pub vars: u32,
+    pub clauses: Vec<Vec<i32>>,
+}
+
+impl Cnf {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn new_var(&mut self) -> i32 {
+        self.vars += 1;
+        self.vars as i32
+    }
+
+    pub fn add(&mut self, clause: impl IntoIterator<Item = i32>) {
+        self.clauses.push(clause.into_iter().collect());
+    }
+
+    pub fn write_dimacs(&self, out: impl Write) -> io::Result<()> {
+        let mut out = BufWriter::new(out);
+        writeln!(out, "p cnf {} {}", self.vars, self.clauses.len())?;
+        for clause in self.clauses.iter() {
+            for lit in clause {
+                write!(out, "{} ", lit)?;
+            }
+            writeln!(out, "0")?;
+        }
+        out.flush()
+    }
+
+    /// Solve it with the built-in solver.
+    pub fn solve(&self) -> Outcome {
+        self.solver().solve()
+    }
+
+    /// A built-in solver with all the clauses in it, for when you want to keep adding
+    /// more, or set a limit.
+    pub fn solver(&self) -> Solver {
+        let mut solver = Solver::new(self.vars);
+        for clause in self.clauses.iter() {
+            solver.add_clause(clause);
+        }
+        solver
+    }
+}
+
+/// Read what a solver printed, in the usual competition format: an `s SATISFIABLE` line
+/// and `v` lines with the values. Variables it doesn't mention are false.
+pub fn read_model(input: impl BufRead, vars: u32) -> io::Result<Outcome> {
+    let bad = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_string());
+    let mut status = None;
+    let mut model = vec![false; vars as usize];
+    for line in input.lines() {
+        let line = line?;
+        if let Some(rest) = line.strip_prefix("s ") {
+            status = Some(match rest.trim() {
+                "SATISFIABLE" => true,
+                "UNSATISFIABLE" => return Ok(Outcome::Unsat),
+                _ => return Ok(Outcome::Unknown),
+            });
+        } else if let Some(rest) = line.strip_prefix("v ") {
+            for lit in rest.split_whitespace() {
+                let lit = lit.parse::<i64>().map_err(|_| bad("bad literal"))?;
+                let var = lit.unsigned_abs() as usize;
+                if var > model.len() {
+                    return Err(bad("variable out of range"));
+                }
+                if var > 0 {
+                    model[var - 1] = lit > 0;
+                }
+            }
+        }
+    }
+    match status {
+        Some(_) => Ok(Outcome::Sat(model)),
+        None => Err(bad("solver didn't say whether it was satisfiable")),
+    }
+}
+
+/// Solve with a solver installed on the system, like `Command::new("kissat")`. The
+/// formula goes in on stdin and the answer gets read from stdout.
+pub fn run_solver(cnf: &Cnf, command: &mut Command) -> io::Result<Outcome> {
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()?;
+    let stdin = child.stdin.take().expect("stdin was piped");
+    let stdout = child.stdout.take().expect("stdout was piped");
+    // Write and read at the same time, so neither side gets stuck on a full pipe
+    let outcome = std::thread::scope(|scope| {
+        let writer = scope.spawn(move || cnf.write_dimacs(stdin));
+        let outcome = read_model(BufReader::new(stdout), cnf.vars);
+        // The solver can quit before reading everything if it finds a quick answer
+        let _ = writer.join().expect("writing the formula panicked");
+        outcome
+    });
+    child.wait()?;
+    outcome
+}
+
+/// Some generations of a region under a rule, as a SAT problem.
+#[derive(Debug, Clone)]
+pub struct Encoding {
+    generations: usize,
+    edges: Vec<EdgePos>,
+    index: AHashMap<EdgePos, usize>,
+    /// The alive and barren variables of each edge, one generation after another.
+    vars: Vec<(i32, i32)>,
+    cnf: Cnf,
+}
+
+impl Encoding {
+    /// Generations 0 up to and including `generations`, each following from the last.
+    pub fn new(rule: Rule, region: &Region, generations: usize) -> Self {
+        let table = rule.compile();
+        let mut coords = region.coords();
+        coords.sort_unstable();
+        let edges = coords
+            .iter()
+            .flat_map(|&coord| {
+                RestrictedHexDir::all()
+                    .into_iter()
+                    .map(move |edge| EdgePos::new_raw(coord, edge))
+            })
+            .collect::<Vec<_>>();
+        let index = edges
+            .iter()
+            .enumerate()
+            .map(|(idx, &pos)| (pos, idx))
+            .collect::<AHashMap<_, _>>();
+
+        let mut cnf = Cnf::new();
+        let mut vars = Vec::new();
+        for _ in 0..=generations {
+            for _ in edges.iter() {
+                let (alive, barren) = (cnf.new_var(), cnf.new_var());
+                cnf.add([-alive, -barren]);
+                vars.push((alive, barren));
+            }
+        }
+        let mut out = Self {
+            generations,
+            edges,
+            index,
+            vars,
+            cnf,
+        };
+
+        // Edges just outside still have to stay dead
+        let mut checked = out.edges.clone();
+        checked.extend(table.boundary(&coords, |pos| out.index.contains_key(&pos)));
+
+        for generation in 0..generations {
+            for &pos in checked.iter() {
+                let neighbors = table
+                    .sources(pos)
+                    .filter_map(|source| out.alive_var(generation, source))
+                    .collect::<Vec<_>>();
+                out.encode_transition(rule, generation, pos, &neighbors);
+            }
+        }
+        out
+    }
+
+    /// Clauses saying how `pos` goes from `generation` to the next one.
+    fn encode_transition(
+        &mut self,
+        rule: Rule,
+        generation: usize,
+        pos: EdgePos,
+        neighbors: &[i32],
+    ) {
+        let here = self.state_vars(generation, pos);
+        let next = self.state_vars(generation + 1, pos);
+        let at_least = self.counter(neighbors);
+
+        // Barren edges always die
+        if let (Some((_, barren)), Some((alive_next, barren_next))) = (here, next) {
+            self.cnf.add([-barren, -alive_next]);
+            self.cnf.add([-barren, -barren_next]);
+        }
+
+        for count in 0..=neighbors.len() {
+            // The clauses all start with "unless there are exactly `count` live neighbors"
+            let mut unless = Vec::new();
+            if count > 0 {
+                unless.push(-at_least[count - 1]);
+            }
+            if count < neighbors.len() {
+                unless.push(at_least[count]);
+            }
+
+            for state in [Aliveness::Dead, Aliveness::Alive] {
+                let mut clause = unless.clone();
+                match (state, here) {
+                    (Aliveness::Dead, Some((alive, barren))) => clause.extend([alive, barren]),
+                    (Aliveness::Alive, Some((alive, _))) => clause.push(-alive),
+                    (Aliveness::Dead, None) => {}
+                    // Outside the region, so it's never alive
+                    _ => continue,
+                }
+
+                let after = rule.transition(state, count as u8);
+                match (after, next) {
+                    (Aliveness::Dead, None) => {}
+                    (_, None) => self.cnf.add(clause),
+                    (Aliveness::Alive, Some((alive, _))) => {
+                        self.cnf.add(clause.iter().copied().chain([alive]))
+                    }
+                    (Aliveness::Barren, Some((_, barren))) => {
+                        self.cnf.add(clause.iter().copied().chain([barren]))
+                    }
+                    (Aliveness::Dead, Some((alive, barren))) => {
+                        self.cnf.add(clause.iter().copied().chain([-alive]));
+                        self.cnf.add(clause.iter().copied().chain([-barren]));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Literals that are true exactly when at least 1, 2, ... of `inputs` are true.
+    fn counter(&mut self, inputs: &[i32]) -> Vec<i32> {
+        let mut prev: Vec<i32> = Vec::new();
+        for &input in inputs {
+            let mut counts = Vec::with_capacity(prev.len() + 1);
+            for k in 0..=prev.len() {
+                // At least k + 1 so far, if there were k + 1 before or k and this one
+                let before = prev.get(k).copied();
+                let one_fewer = k.checked_sub(1).map(|k| prev[k]);
+                if before.is_none() && one_fewer.is_none() {
+                    counts.push(input);
+                    continue;
+                }
+                let out = self.cnf.new_var();
+                if let Some(before) = before {
+                    self.cnf.add([-before, out]);
+                }
+                self.cnf.add(
+                    [-input]
+                        .into_iter()
+                        .chain(one_fewer.map(|lit| -lit))
+                        .chain([out]),
+                );
+                self.cnf.add([-out, input].into_iter().chain(before));
+                if let Some(one_fewer) = one_fewer {
+                    self.cnf.add([-out, one_fewer].into_iter().chain(before));
+                }
+                counts.push(out);
+            }
+            prev = counts;
+        }
+        prev
+    }
+
+    fn state_vars(&self, generation: usize, pos: EdgePos) -> Option<(i32, i32)> {
+        let idx = *self.index.get(&pos)?;
+        Some(self.vars[generation * self.edges.len() + idx])
+    }
+
+    pub fn generations(&self) -> usize {
+        self.generations
+    }
+
+    /// The edges in the region.
+    pub fn edges(&self) -> &[EdgePos] {
+        &self.edges
+    }
+
+    pub fn cnf(&self) -> &Cnf {
+        &self.cnf
+    }
+
+    /// For adding constraints of your own.
+    pub fn cnf_mut(&mut self) -> &mut Cnf {
+        &mut self.cnf
+    }
+
+    /// The variable for `pos` being alive in `generation`, if it's in the region.
+    pub fn alive_var(&self, generation: usize, pos: EdgePos) -> Option<i32> {
+        self.state_vars(generation, pos).map(|(alive, _)| alive)
+    }
+
+    /// The variable for `pos` being barren in `generation`, if it's in the region.
+    pub fn barren_var(&self, generation: usize, pos: EdgePos) -> Option<i32> {
+        self.state_vars(generation, pos).map(|(_, barren)| barren)
+    }
+
+    /// Make `pos` be `state` in `generation`. Anything but dead outside the region can't
+    /// happen.
+    pub fn fix(&mut self, generation: usize, pos: EdgePos, state: Aliveness) {
+        match (self.state_vars(generation, pos), state) {
+            (None, Aliveness::Dead) => {}
+            (None, _) => self.cnf.add([]),
+            (Some((alive, barren)), Aliveness::Dead) => {
+                self.cnf.add([-alive]);
+                self.cnf.add([-barren]);
+            }
+            (Some((alive, barren)), Aliveness::Barren) => {
+                self.cnf.add([-alive]);
+                self.cnf.add([barren]);
+            }
+            (Some((alive, barren)), Aliveness::Alive) => {
+                self.cnf.add([alive]);
+                self.cnf.add([-barren]);
+            }
+        }
+    }
+
+    /// Make `generation` be exactly `board`.
+    pub fn fix_board(&mut self, generation: usize, board: &Board) {
+        for (pos, state) in board.edges() {
+            if !self.index.contains_key(&pos) {
+                self.fix(generation, pos, state);
+            }
+        }
+        for idx in 0..self.edges.len() {
+            let pos = self.edges[idx];
+            self.fix(generation, pos, board.get_liveness(pos));
+        }
+    }
+
+    /// Make two edges be the same, possibly in different generations.
+    pub fn same(&mut self, generation_a: usize, a: EdgePos, generation_b: usize, b: EdgePos) {
+        match (
+            self.state_vars(generation_a, a),
+            self.state_vars(generation_b, b),
+        ) {
+            (Some((alive_a, barren_a)), Some((alive_b, barren_b))) => {
+                self.cnf.add([-alive_a, alive_b]);
+                self.cnf.add([alive_a, -alive_b]);
+                self.cnf.add([-barren_a, barren_b]);
+                self.cnf.add([barren_a, -barren_b]);
+            }
+            (Some(_), None) => self.fix(generation_a, a, Aliveness::Dead),
+            (None, Some(_)) => self.fix(generation_b, b, Aliveness::Dead),
+            (None, None) => {}
+        }
+    }
+
+    /// Make something in `generation` be alive.
+    pub fn require_alive(&mut self, generation: usize) {
+        let clause = self
+            .edges
+            .iter()
+            .filter_map(|&pos| self.alive_var(generation, pos))
+            .collect::<Vec<_>>();
+        self.cnf.add(clause);
+    }
+
+    /// Rule out the exact solution `model` gave for `generation`, so solving again finds
+    /// a different one.
+    pub fn exclude(&mut self, model: &[bool], generation: usize) {
+        let clause = (0..self.edges.len())
+            .flat_map(|idx| {
+                let (alive, barren) = self.vars[generation * self.edges.len() + idx];
+                [alive, barren]
+            })
+            .map(|var| if model[var as usize - 1] { -var } else { var })
+            .collect::<Vec<_>>();
+        self.cnf.add(clause);
+    }
+
+    /// What `generation` looks like in a solution.
+    pub fn decode(&self, model: &[bool], generation: usize) -> Board {
+        let mut board = Board::new();
+        for (idx, &pos) in self.edges.iter().enumerate() {
+            let (alive, barren) = self.vars[generation * self.edges.len() + idx];
+            if model[alive as usize - 1] {
+                board.set_alive(pos, Aliveness::Alive);
+            } else if model[barren as usize - 1] {
+                board.set_alive(pos, Aliveness::Barren);
+            }
+        }
+        board
+    }
+
+    /// Every generation of a solution.
+    pub fn decode_all(&self, model: &[bool]) -> Vec<Board> {
+        (0..=self.generations)
+            .map(|generation| self.decode(model, generation))
+            .collect()
+    }
+}
+
+/// Something that comes back after `period` generations moved over by `displacement`:
+/// a spaceship, or an oscillator if `displacement` is zero. Generation 0 has to have
+/// something alive in it.
+///
+/// It could turn out to have a smaller period, so check what comes out.
+pub fn periodic(rule: Rule, region: &Region, period: usize, displacement: HexCoord) -> Encoding {
+    let mut out = Encoding::new(rule, region, period);
+    let edges = out.edges.clone();
+    for &pos in edges.iter() {
+        let moved = EdgePos::new_raw(pos.coord() + displacement, pos.edge());
+        out.same(0, pos, period, moved);
+        let from = EdgePos::new_raw(pos.coord() - displacement, pos.edge());
+        if !out.index.contains_key(&from) {
+            out.fix(period, pos, Aliveness::Dead);
+        }
+    }
+    out.require_alive(0);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        soup::{Density, SoupSymmetry},
+        NeighborRegion,
+    };
+    use ahash::AHashSet;
+
+    fn binomial(n: u64, k: u64) -> u64 {
+        (0..k).fold(1, |acc, i| acc * (n - i) / (i + 1))
+    }
+
+    /// An encoding with nothing in it, to build counters on.
+    fn empty() -> Encoding {
+        let rule = Rule::new_raw(0, 0, NeighborRegion::Six);
+        Encoding::new(rule, &Region::Coords(AHashSet::new()), 0)
+    }
+
+    #[test]
+    fn counter_counts() {
+        for n in 1..=6 {
+            let mut encoding = empty();
+            let inputs = (0..n).map(|_| encoding.cnf.new_var()).collect::<Vec<_>>();
+            let at_least = encoding.counter(&inputs);
+            assert_eq!(at_least.len(), n);
+            for bits in 0u32..1 << n {
+                let mut solver = encoding.cnf.solver();
+                for (idx, &input) in inputs.iter().enumerate() {
+                    solver.add_clause(&[if bits >> idx & 1 == 1 { input } else { -input }]);
+                }
+                let Outcome::Sat(model) = solver.solve() else {
+                    panic!("{} inputs set to {:b} should be fine", n, bits);
+                };
+                for (k, &lit) in at_least.iter().enumerate() {
+                    let value = model[lit as usize - 1];
+                    assert_eq!(value, bits.count_ones() as usize > k, "{:b} {}", bits, k);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn at_most_and_exactly() {
+        for n in 1..=6u64 {
+            for k in 0..=n {
+                let mut encoding = empty();
+                let inputs = (0..n).map(|_| encoding.cnf.new_var()).collect::<Vec<_>>();
+                let at_least = encoding.counter(&inputs);
+                // Models that differ in the inputs
+                let count = |cnf: &Cnf| {
+                    let mut solver = cnf.solver();
+                    let mut found = 0;
+                    while let Outcome::Sat(model) = solver.solve() {
+                        found += 1;
+                        let block = inputs
+                            .iter()
+                            .map(|&input| {
+                                if model[input as usize - 1] {
+                                    -input
+                                } else {
+                                    input
+                                }
+                            })
+                            .collect::<Vec<_>>();
+                        solver.add_clause(&block);
+                    }
+                    found
+                };
+
+                let mut at_most = encoding.cnf.clone();
+                if k < n {
+                    at_most.add([-at_least[k as usize]]);
+                }
+                let expected = (0..=k).map(|i| binomial(n, i)).sum::<u64>();
+                assert_eq!(count(&at_most), expected, "at most {} of {}", k, n);
+
+                let mut exactly = at_most;
+                if k > 0 {
+                    exactly.add([at_least[k as usize - 1]]);
+                }
+                assert_eq!(count(&exactly), binomial(n, k), "exactly {} of {}", k, n);
+            }
+        }
+    }
+
+    #[test]
+    fn decodes_what_the_rule_does() {
+        let region = Region::Parallelogram {
+            corner: HexCoord::new(0, 0),
+            width: 9,
+            height: 9,
+        };
+        let middle = Region::Parallelogram {
+            corner: HexCoord::new(3, 3),
+            width: 3,
+            height: 3,
+        };
+        for (seed, rule) in ["B2/S3/@8*", "B24/S35/@8*", "B2/S34/@6", "B3/S23/@4"]
+            .into_iter()
+            .enumerate()
+        {
+            let rule: Rule = rule.parse().unwrap();
+            let mut board = Board::new();
+            board.random_fill(&middle, Density::default(), SoupSymmetry::None, seed as u64);
+            let mut encoding = Encoding::new(rule, &region, 2);
+            encoding.fix_board(0, &board);
+            let Outcome::Sat(model) = encoding.cnf().solve() else {
+                panic!("{} should have a solution", rule);
+            };
+            let decoded = encoding.decode_all(&model);
+            for generation in decoded {
+                assert!(generation == board, "{}", rule);
+                board.apply_rule(rule);
+            }
+        }
+    }
+}
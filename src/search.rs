@@ -0,0 +1,601 @@
+//! Finding every still life or oscillator that fits in a region.
+//!
+//! Every edge in the region gets a variable for each phase, which can be any of the three
+//! states. Everything outside the region is dead. For each phase and each edge in or next
+//! to the region, the rule says what the edge has to be next phase given how many of its
+//! neighbors are alive now; the search keeps narrowing down the variables with those
+//! constraints and backtracks when one can't be met.
+//!
+//! Turning something around or moving it over gives the same object, so whenever that's
+//! sure to give something that still fits and comes earlier, the search backs off and
+//! leaves it to be found that way instead.
+
+use ahash::{AHashMap, AHashSet};
+
+use crate::{
+    math::{Aliveness, EdgePos, HexCoord, RestrictedHexDir},
+    pattern::Pattern,
+    region::Region,
+    symmetry::Transform,
+    transition::TransitionTable,
+    Board, Rule,
+};
+
+const DEAD: u8 = 1 << Aliveness::Dead as u8;
+const BARREN: u8 = 1 << Aliveness::Barren as u8;
+const ALIVE: u8 = 1 << Aliveness::Alive as u8;
+const STATES: [Aliveness; 3] = [Aliveness::Dead, Aliveness::Barren, Aliveness::Alive];
+
+/// What to look for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchConfig {
+    pub rule: Rule,
+    /// Everything has to fit in here.
+    pub region: Region,
+    /// 1 for still lifes. Things with a smaller period that divides this one don't count.
+    pub period: u64,
+    /// Most live edges allowed in the first phase.
+    pub max_population: usize,
+}
+
+impl SearchConfig {
+    pub fn new(rule: Rule, region: Region, period: u64) -> Self {
+        Self {
+            rule,
+            region,
+            period,
+            max_population: usize::MAX,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Slot {
+    Var(usize),
+    Dead,
+}
+
+/// One edge in one phase has to turn into what it is next phase.
+#[derive(Debug, Clone)]
+struct Constraint {
+    here: Slot,
+    next: Slot,
+    neighbors: Vec<Slot>,
+}
+
+/// A way of turning things around and moving them over.
+struct Symmetry {
+    /// For each variable, the one that ends up there, if any.
+    from: Vec<Option<usize>>,
+    /// Variables that get moved out of the region.
+    lost: Vec<usize>,
+}
+
+struct Frame {
+    var: usize,
+    /// Values not tried yet.
+    untried: u8,
+    trail_len: usize,
+}
+
+/// A backtracking search, handing out each different object it finds.
+///
+/// Rotations, reflections and translations of something already found, and other phases
+/// of it, get skipped.
+pub struct Search {
+    rule: Rule,
+    period: usize,
+    max_population: usize,
+    edges: Vec<EdgePos>,
+    constraints: Vec<Constraint>,
+    /// Which constraints each variable is in.
+    watchers: Vec<Vec<usize>>,
+    domains: Vec<u8>,
+    /// Variables and what they were before being narrowed down.
+    trail: Vec<(usize, u8)>,
+    stack: Vec<Frame>,
+    /// For each of the first row and column of a parallelogram, the edges in it.
+    edge_lines: Vec<Vec<usize>>,
+    symmetries: Vec<Symmetry>,
+    seen: AHashSet<Vec<(i64, i64, u8)>>,
+    started: bool,
+}
+
+impl Search {
+    pub fn new(config: &SearchConfig) -> Self {
+        let rule = config.rule;
+        let table = rule.compile();
+        let period = config.period.max(1) as usize;
+
+        let mut coords = config.region.coords();
+        coords.sort_unstable();
+        let edges = coords
+            .iter()
+            .flat_map(|&coord| {
+                RestrictedHexDir::all()
+                    .into_iter()
+                    .map(move |edge| EdgePos::new_raw(coord, edge))
+            })
+            .collect::<Vec<_>>();
+        let index = edges
+            .iter()
+            .enumerate()
+            .map(|(idx, &pos)| (pos, idx))
+            .collect::<AHashMap<_, _>>();
+        let count = edges.len();
+        let slot = |pos: EdgePos, phase: usize| match index.get(&pos) {
+            Some(&idx) => Slot::Var(phase * count + idx),
+            None => Slot::Dead,
+        };
+
+        // Edges outside the region still have to stay dead
+        let mut checked = edges.clone();
+        checked.extend(table.boundary(&coords, |pos| index.contains_key(&pos)));
+
+        let mut constraints = Vec::new();
+        for phase in 0..period {
+            for &pos in checked.iter() {
+                constraints.push(Constraint {
+                    here: slot(pos, phase),
+                    next: slot(pos, (phase + 1) % period),
+                    neighbors: table
+                        .sources(pos)
+                        .map(|source| slot(source, phase))
+                        .filter(|&slot| slot != Slot::Dead)
+                        .collect(),
+                });
+            }
+        }
+        let mut watchers = vec![Vec::new(); count * period];
+        for (idx, constraint) in constraints.iter().enumerate() {
+            let slots = [constraint.here, constraint.next]
+                .into_iter()
+                .chain(constraint.neighbors.iter().copied());
+            for slot in slots {
+                if let Slot::Var(var) = slot {
+                    if watchers[var].last() != Some(&idx) {
+                        watchers[var].push(idx);
+                    }
+                }
+            }
+        }
+
+        // Anything that doesn't touch the first row and column of a parallelogram could
+        // be moved over, so it's a duplicate
+        let edge_lines = match &config.region {
+            Region::Parallelogram { corner, .. } => {
+                let line = |on_line: &dyn Fn(HexCoord) -> bool| {
+                    (0..period)
+                        .flat_map(|phase| {
+                            edges
+                                .iter()
+                                .enumerate()
+                                .filter(|(_, pos)| on_line(pos.coord()))
+                                .map(move |(idx, _)| phase * count + idx)
+                        })
+                        .collect::<Vec<_>>()
+                };
+                vec![
+                    line(&|coord| coord.y == corner.y),
+                    line(&|coord| coord.x == corner.x),
+                ]
+            }
+            _ => Vec::new(),
+        };
+
+        // Anything turned around or moved over that still fits is also a solution, so
+        // only keep whichever comes first. Ones that move much of the region out of it
+        // hardly ever fit, so they aren't worth checking.
+        let symmetries = Transform::symmetries()
+            .into_iter()
+            .filter(|&symmetry| keeps_rule(&table, symmetry))
+            .flat_map(|symmetry| {
+                let offsets = coords
+                    .iter()
+                    .flat_map(|&to| {
+                        coords
+                            .iter()
+                            .map(move |&from| to - symmetry.apply_coord(from))
+                    })
+                    .collect::<AHashSet<_>>();
+                offsets
+                    .into_iter()
+                    .map(move |offset| symmetry.then(Transform::translate(offset)))
+            })
+            .filter(|&symmetry| symmetry != Transform::identity())
+            .filter_map(|symmetry| {
+                let moved = edges
+                    .iter()
+                    .map(|&pos| index.get(&symmetry.apply_edge(pos)).copied())
+                    .collect::<Vec<_>>();
+                if 3 * moved.iter().flatten().count() < 2 * count {
+                    return None;
+                }
+                let mut from = vec![None; count * period];
+                let mut lost = Vec::new();
+                for phase in 0..period {
+                    for (idx, &to) in moved.iter().enumerate() {
+                        match to {
+                            Some(to) => from[phase * count + to] = Some(phase * count + idx),
+                            None => lost.push(phase * count + idx),
+                        }
+                    }
+                }
+                Some(Symmetry { from, lost })
+            })
+            .collect();
+
+        // Barren edges always die, so they can't be in still lifes
+        let start = if period == 1 {
+            DEAD | ALIVE
+        } else {
+            DEAD | BARREN | ALIVE
+        };
+        Self {
+            rule,
+            period,
+            max_population: config.max_population,
+            edges,
+            constraints,
+            watchers,
+            domains: vec![start; count * period],
+            trail: Vec::new(),
+            stack: Vec::new(),
+            edge_lines,
+            symmetries,
+            seen: AHashSet::new(),
+            started: false,
+        }
+    }
+
+    /// Narrow a variable down, returning whether it changed.
+    fn narrow(&mut self, var: usize, allowed: u8) -> bool {
+        let old = self.domains[var];
+        let new = old & allowed;
+        if new == old {
+            return false;
+        }
+        self.trail.push((var, old));
+        self.domains[var] = new;
+        true
+    }
+
+    fn domain(&self, slot: Slot) -> u8 {
+        match slot {
+            Slot::Var(var) => self.domains[var],
+            Slot::Dead => DEAD,
+        }
+    }
+
+    /// Narrow everything down as far as the constraints touching `changed` allow.
+    /// Returns false if something can't be anything.
+    fn propagate(&mut self, changed: impl IntoIterator<Item = usize>) -> bool {
+        let mut queue = Vec::new();
+        let mut queued = vec![false; self.constraints.len()];
+        for var in changed {
+            for &idx in self.watchers[var].iter() {
+                if !queued[idx] {
+                    queued[idx] = true;
+                    queue.push(idx);
+                }
+            }
+        }
+
+        while let Some(idx) = queue.pop() {
+            queued[idx] = false;
+            let Some(touched) = self.revise(idx) else {
+                return false;
+            };
+            for var in touched {
+                for &other in self.watchers[var].iter() {
+                    if !queued[other] {
+                        queued[other] = true;
+                        queue.push(other);
+                    }
+                }
+            }
+        }
+        self.still_possible()
+    }
+
+    /// Narrow down the variables in one constraint, returning which ones changed, or
+    /// `None` if it can't be met.
+    fn revise(&mut self, idx: usize) -> Option<Vec<usize>> {
+        let constraint = &self.constraints[idx];
+        let (here, next) = (constraint.here, constraint.next);
+        let mut known = 0;
+        let mut unknown = Vec::new();
+        for &slot in constraint.neighbors.iter() {
+            let domain = self.domain(slot);
+            if domain == ALIVE {
+                known += 1;
+            } else if domain & ALIVE != 0 {
+                if let Slot::Var(var) = slot {
+                    unknown.push(var);
+                }
+            }
+        }
+
+        let (here_domain, next_domain) = (self.domain(here), self.domain(next));
+        let mut here_ok = 0;
+        let mut next_ok = 0;
+        let (mut fewest, mut most) = (usize::MAX, 0);
+        for state in STATES {
+            if here_domain & (1 << state as u8) == 0 {
+                continue;
+            }
+            for count in known..=known + unknown.len() {
+                let after = self.rule.transition(state, count as u8);
+                // In a still life this is the same variable
+                let fits = if here == next {
+                    after == state
+                } else {
+                    next_domain & (1 << after as u8) != 0
+                };
+                if fits {
+                    here_ok |= 1 << state as u8;
+                    next_ok |= 1 << after as u8;
+                    fewest = fewest.min(count);
+                    most = most.max(count);
+                }
+            }
+        }
+        if here_ok == 0 {
+            return None;
+        }
+
+        let mut touched = Vec::new();
+        if let Slot::Var(var) = here {
+            if self.narrow(var, here_ok) {
+                touched.push(var);
+            }
+        }
+        if let Slot::Var(var) = next {
+            if self.narrow(var, next_ok) {
+                touched.push(var);
+            }
+        }
+        if !unknown.is_empty() {
+            let allowed = if fewest == known + unknown.len() {
+                Some(ALIVE)
+            } else if most == known {
+                Some(DEAD | BARREN)
+            } else {
+                None
+            };
+            if let Some(allowed) = allowed {
+                for var in unknown {
+                    if self.narrow(var, allowed) {
+                        touched.push(var);
+                    }
+                }
+            }
+        }
+        if touched.iter().any(|&var| self.domains[var] == 0) {
+            return None;
+        }
+        Some(touched)
+    }
+
+    /// Checks that aren't about any one constraint.
+    fn still_possible(&self) -> bool {
+        let alive = self.domains[..self.edges.len()]
+            .iter()
+            .filter(|&&domain| domain == ALIVE)
+            .count();
+        if alive > self.max_population {
+            return false;
+        }
+        self.edge_lines
+            .iter()
+            .all(|line| line.iter().any(|&var| self.domains[var] != DEAD))
+            && !self
+                .symmetries
+                .iter()
+                .any(|symmetry| self.turns_into_earlier(symmetry))
+    }
+
+    /// Whether turning things around is sure to give something that still fits and comes
+    /// first, in which case that'll be found instead.
+    ///
+    /// Things come first when their first edge that's different is more alive. Moving
+    /// something over toward the start of the region only makes it come earlier, so this
+    /// never throws away the one that touches the first row and column of a parallelogram.
+    fn turns_into_earlier(&self, symmetry: &Symmetry) -> bool {
+        if symmetry.lost.iter().any(|&var| self.domains[var] != DEAD) {
+            return false;
+        }
+        for (var, &from) in symmetry.from.iter().enumerate() {
+            let here = self.domains[var];
+            let there = from.map_or(DEAD, |from| self.domains[from]);
+            if here == there && here.is_power_of_two() {
+                continue;
+            }
+            // The least alive it could be after turning against the most alive it is now
+            return there.trailing_zeros() > 7 - here.leading_zeros();
+        }
+        false
+    }
+
+    fn undo_to(&mut self, len: usize) {
+        while self.trail.len() > len {
+            let (var, old) = self.trail.pop().unwrap();
+            self.domains[var] = old;
+        }
+    }
+
+    /// Try the next untried value of the top frame, popping frames that run out.
+    /// Returns false when there's nothing left to try at all.
+    fn advance(&mut self) -> bool {
+        while let Some(frame) = self.stack.last_mut() {
+            if frame.untried == 0 {
+                let trail_len = frame.trail_len;
+                self.stack.pop();
+                self.undo_to(trail_len);
+                continue;
+            }
+            let bit = frame.untried & frame.untried.wrapping_neg();
+            frame.untried &= !bit;
+            let (var, trail_len) = (frame.var, frame.trail_len);
+            self.undo_to(trail_len);
+            self.narrow(var, bit);
+            if self.propagate([var]) {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// The phases of the current complete assignment.
+    fn phases(&self) -> Vec<Board> {
+        let count = self.edges.len();
+        (0..self.period)
+            .map(|phase| {
+                let mut board = Board::new();
+                for (idx, &pos) in self.edges.iter().enumerate() {
+                    let domain = self.domains[phase * count + idx];
+                    let state = STATES[domain.trailing_zeros() as usize];
+                    board.set_alive(pos, state);
+                }
+                board
+            })
+            .collect()
+    }
+
+    /// If the current complete assignment is something new, remember it and hand it back.
+    fn accept(&mut self) -> Option<Pattern> {
+        let phases = self.phases();
+        if phases[0].population() == 0 {
+            return None;
+        }
+        // It's really got a smaller period
+        if (1..self.period).any(|d| self.period.is_multiple_of(d) && phases[d] == phases[0]) {
+            return None;
+        }
+        let key = phases
+            .iter()
+            .map(|phase| phase.canonical().0.sorted_cells())
+            .min()
+            .expect("there's at least one phase");
+        self.seen
+            .insert(key)
+            .then(|| Pattern::from_board(&phases[0]))
+    }
+}
+
+/// Whether the rule works the same way after turning things around.
+fn keeps_rule(table: &TransitionTable, symmetry: Transform) -> bool {
+    RestrictedHexDir::all().into_iter().all(|edge| {
+        let pos = EdgePos::new_raw(HexCoord::new(0, 0), edge);
+        let turned = table
+            .sources(pos)
+            .map(|source| symmetry.apply_edge(source))
+            .collect::<AHashSet<_>>();
+        turned == table.sources(symmetry.apply_edge(pos)).collect()
+    })
+}
+
+impl Iterator for Search {
+    type Item = Pattern;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if !self.started {
+            self.started = true;
+            if !self.propagate(0..self.domains.len()) {
+                return None;
+            }
+        } else if !self.advance() {
+            return None;
+        }
+
+        loop {
+            // Branch on whatever's least decided, earliest first
+            let undecided = (0..self.domains.len())
+                .filter(|&var| self.domains[var].count_ones() > 1)
+                .min_by_key(|&var| self.domains[var].count_ones());
+            match undecided {
+                Some(var) => {
+                    self.stack.push(Frame {
+                        var,
+                        untried: self.domains[var],
+                        trail_len: self.trail.len(),
+                    });
+                }
+                None => {
+                    if let Some(found) = self.accept() {
+                        return Some(found);
+                    }
+                }
+            }
+            if !self.advance() {
+                return None;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::code;
+
+    fn key(board: &Board) -> Vec<(i64, i64, u8)> {
+        board.canonical().0.sorted_cells()
+    }
+
+    /// Every still life in the region, by trying everything.
+    fn brute_force(rule: Rule, region: &Region) -> AHashSet<Vec<(i64, i64, u8)>> {
+        let table = rule.compile();
+        let mut coords = region.coords();
+        coords.sort_unstable();
+        let edges = coords
+            .iter()
+            .flat_map(|&coord| RestrictedHexDir::all().map(|edge| EdgePos::new_raw(coord, edge)))
+            .collect::<Vec<_>>();
+        let mut found = AHashSet::new();
+        for mask in 1u32..1 << edges.len() {
+            let mut board = Board::new();
+            for (idx, &pos) in edges.iter().enumerate() {
+                if mask & (1 << idx) != 0 {
+                    board.set_alive(pos, Aliveness::Alive);
+                }
+            }
+            let mut next = board.clone();
+            next.apply_table(&table);
+            if next == board {
+                found.insert(key(&board));
+            }
+        }
+        found
+    }
+
+    #[test]
+    fn finds_every_still_life_once() {
+        let boxes = [
+            Region::between(HexCoord::new(0, 0), HexCoord::new(1, 1)),
+            Region::between(HexCoord::new(0, 0), HexCoord::new(0, 3)),
+        ];
+        for rule in ["B24/S23/@6", "B3/S12/@4", "B2/S123/@10"] {
+            let rule: Rule = rule.parse().unwrap();
+            for region in boxes.iter() {
+                let found = Search::new(&SearchConfig::new(rule, region.clone(), 1))
+                    .map(|pattern| key(&pattern.to_board()))
+                    .collect::<Vec<_>>();
+                let distinct = found.iter().cloned().collect::<AHashSet<_>>();
+                assert_eq!(found.len(), distinct.len(), "{rule} found something twice");
+                assert!(distinct == brute_force(rule, region), "{rule} {region:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn finds_a_known_still_life_once() {
+        let rule: Rule = "B2/S23/@8*".parse().unwrap();
+        let region = Region::between(HexCoord::new(0, 0), HexCoord::new(2, 2));
+        let wanted = key(&code::decode_pattern("0kiz2kiz2").unwrap().to_board());
+        let found = Search::new(&SearchConfig::new(rule, region, 1))
+            .filter(|pattern| key(&pattern.to_board()) == wanted)
+            .count();
+        assert_eq!(found, 1);
+    }
+}
@@ -0,0 +1,259 @@
+//! Driving a board forward under a rule.
+
+use crate::{
+    math::Aliveness,
+    stop::{Outcome, StopConfig, StopDetector},
+    transition::TransitionTable,
+    Board, Rule,
+};
+
+/// Running statistics about a simulation.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Stats {
+    /// Live edges as of the last step.
+    pub population: u64,
+    /// Most live edges seen at once.
+    pub peak_population: u64,
+    /// Edges that became alive in the last step.
+    pub births: u64,
+    /// Edges that stopped being alive in the last step.
+    pub deaths: u64,
+    pub total_births: u64,
+    pub total_deaths: u64,
+}
+
+/// Something to call after every step.
+pub type Hook = Box<dyn FnMut(&Simulation)>;
+
+/// A board, the rule it's running under, and how far along it is.
+pub struct Simulation {
+    board: Board,
+    rule: Rule,
+    table: TransitionTable,
+    generation: u64,
+    stats: Stats,
+    detector: StopDetector,
+    outcome: Outcome,
+    hooks: Vec<Hook>,
+}
+
+impl Simulation {
+    pub fn new(board: Board, rule: Rule) -> Self {
+        let population = board.population() as u64;
+        let table = rule.compile();
+        let mut detector = StopDetector::default();
+        let outcome = detector.observe(&board, 0, &table);
+        Self {
+            board,
+            rule,
+            table,
+            generation: 0,
+            stats: Stats {
+                population,
+                peak_population: population,
+                ..Default::default()
+            },
+            detector,
+            outcome,
+            hooks: Vec::new(),
+        }
+    }
+
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    /// Edit the board, staying at the same generation.
+    ///
+    /// The edited board isn't where the old one was heading, so the stop detector starts
+    /// over.
+    pub fn edit<T>(&mut self, edit: impl FnOnce(&mut Board) -> T) -> T {
+        let out = edit(&mut self.board);
+        self.stats.population = self.board.population() as u64;
+        self.stats.peak_population = self.stats.peak_population.max(self.stats.population);
+        self.detector.reset();
+        self.outcome = self
+            .detector
+            .observe(&self.board, self.generation, &self.table);
+        out
+    }
+
+    /// Replace the board, starting back at generation 0.
+    pub fn set_board(&mut self, board: Board) {
+        let population = board.population() as u64;
+        self.board = board;
+        self.generation = 0;
+        self.stats = Stats {
+            population,
+            peak_population: population,
+            ..Default::default()
+        };
+        self.detector.reset();
+        self.outcome = self.detector.observe(&self.board, 0, &self.table);
+    }
+
+    pub fn rule(&self) -> Rule {
+        self.rule
+    }
+
+    /// Change the rule for future steps.
+    ///
+    /// What the board was doing under the old rule says nothing about what it'll do
+    /// under the new one, so the stop detector starts over.
+    pub fn set_rule(&mut self, rule: Rule) {
+        self.rule = rule;
+        self.table = rule.compile();
+        self.board.wake_all();
+        self.detector.reset();
+        self.outcome = self
+            .detector
+            .observe(&self.board, self.generation, &self.table);
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    pub fn stats(&self) -> &Stats {
+        &self.stats
+    }
+
+    /// What the board seemed to be doing as of the last step.
+    pub fn outcome(&self) -> Outcome {
+        self.outcome
+    }
+
+    /// Change how hard to look for the board settling down.
+    ///
+    /// This forgets everything seen so far.
+    pub fn set_stop_config(&mut self, config: StopConfig) {
+        self.detector = StopDetector::new(config);
+        self.outcome = self
+            .detector
+            .observe(&self.board, self.generation, &self.table);
+    }
+
+    /// Call the hook after every step from now on.
+    pub fn add_hook(&mut self, hook: impl FnMut(&Simulation) + 'static) {
+        self.hooks.push(Box::new(hook));
+    }
+
+    /// Advance one generation, returning whether it looks like it's settled down.
+    pub fn step(&mut self) -> Outcome {
+        let changes = self.board.step_changes(&self.table);
+        self.generation += 1;
+
+        let births = changes
+            .iter()
+            .filter(|(_, _, new)| *new == Aliveness::Alive)
+            .count() as u64;
+        let deaths = changes
+            .iter()
+            .filter(|(_, old, _)| *old == Aliveness::Alive)
+            .count() as u64;
+        let stats = &mut self.stats;
+        stats.population = self.board.population() as u64;
+        stats.peak_population = stats.peak_population.max(stats.population);
+        stats.births = births;
+        stats.deaths = deaths;
+        stats.total_births += births;
+        stats.total_deaths += deaths;
+        self.outcome = self
+            .detector
+            .observe(&self.board, self.generation, &self.table);
+
+        // Take the hooks out so they can look at the whole simulation
+        let mut hooks = std::mem::take(&mut self.hooks);
+        for hook in hooks.iter_mut() {
+            hook(self);
+        }
+        self.hooks = hooks;
+
+        self.outcome
+    }
+
+    /// Advance `n` generations.
+    pub fn step_n(&mut self, n: u64) {
+        for _ in 0..n {
+            self.step();
+        }
+    }
+
+    /// Step until the board dies out, stops or starts repeating itself, but give up after
+    /// `max_steps` steps.
+    pub fn run_until_settled(&mut self, max_steps: u64) -> Outcome {
+        for _ in 0..max_steps {
+            if self.outcome.is_settled() {
+                break;
+            }
+            self.step();
+        }
+        self.outcome
+    }
+
+    /// Step until `done` returns true, checking before every step, but give up after
+    /// `max_steps` steps.
+    ///
+    /// Returns the generation it finished on, or `None` if it gave up.
+    pub fn run_until(
+        &mut self,
+        max_steps: u64,
+        mut done: impl FnMut(&Simulation) -> bool,
+    ) -> Option<u64> {
+        for _ in 0..max_steps {
+            if done(self) {
+                return Some(self.generation);
+            }
+            self.step();
+        }
+        done(self).then_some(self.generation)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        math::{EdgePos, HexCoord, RestrictedHexDir},
+        NeighborRegion,
+    };
+
+    #[test]
+    fn changing_the_rule_starts_over() {
+        let mut board = Board::new();
+        board.set_alive(
+            EdgePos::new_raw(HexCoord::new(0, 0), RestrictedHexDir::XY),
+            Aliveness::Alive,
+        );
+        let mut sim = Simulation::new(board, Rule::new_raw(0, 1, NeighborRegion::Six));
+        sim.step_n(3);
+        assert!(matches!(sim.outcome(), Outcome::Stable { .. }));
+
+        sim.set_rule(Rule::new_raw(0, 0, NeighborRegion::Six));
+        assert_eq!(sim.outcome(), Outcome::Running);
+        sim.step();
+        assert_eq!(sim.board().population(), 0);
+        sim.step();
+        assert!(matches!(sim.outcome(), Outcome::Extinct { .. }));
+    }
+
+    #[test]
+    fn editing_starts_over() {
+        let pos = EdgePos::new_raw(HexCoord::new(0, 0), RestrictedHexDir::XY);
+        let mut sim = Simulation::new(Board::new(), Rule::new_raw(0, 1, NeighborRegion::Six));
+        sim.step_n(2);
+        assert_eq!(sim.outcome(), Outcome::Extinct { gen: 0 });
+
+        sim.edit(|board| board.set_alive(pos, Aliveness::Alive));
+        assert_eq!(sim.outcome(), Outcome::Running);
+        assert_eq!(sim.stats().population, 1);
+        sim.step();
+        assert_eq!(sim.outcome(), Outcome::Stable { gen: 2 });
+
+        // Whatever it was doing before doesn't count as repeating
+        sim.edit(|board| board.set_alive(pos, Aliveness::Dead));
+        sim.edit(|board| board.set_alive(pos, Aliveness::Alive));
+        assert_eq!(sim.outcome(), Outcome::Running);
+        assert_eq!(sim.run_until_settled(10), Outcome::Stable { gen: 3 });
+    }
+}
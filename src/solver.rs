@@ -0,0 +1,578 @@
+//! A small CDCL SAT solver, so SAT searches work without installing anything.
+//!
+//! It's nowhere near as fast as a real solver like kissat or cadical, but it does the
+//! usual things: two watched literals, learning clauses from conflicts, picking variables
+//! that were in recent conflicts, remembering which way variables were last set, and
+//! restarting now and then. [`crate::sat::run_solver`] can use an installed solver
+//! instead.
+
+/// What a solver came up with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Outcome {
+    /// The value of every variable, with variable `n` at index `n - 1`.
+    Sat(Vec<bool>),
+    Unsat,
+    /// Gave up.
+    Unknown,
+}
+
+/// Literals are `2 * var + 1` if they're negated, `2 * var` if not, with 0-based vars.
+type Lit = u32;
+
+fn lit_from_dimacs(lit: i32) -> Lit {
+    let var = lit.unsigned_abs() - 1;
+    2 * var + (lit < 0) as u32
+}
+
+fn var_of(lit: Lit) -> usize {
+    (lit >> 1) as usize
+}
+
+struct Clause {
+    /// The first two are the ones being watched. In a clause that's the reason for
+    /// something, the first one is what it implied.
+    lits: Vec<Lit>,
+    learnt: bool,
+}
+
+pub struct Solver {
+    clauses: Vec<Clause>,
+    /// For each literal, the clauses that need looking at when it becomes false.
+    watches: Vec<Vec<usize>>,
+    values: Vec<Option<bool>>,
+    levels: Vec<usize>,
+    reasons: Vec<Option<usize>>,
+    trail: Vec<Lit>,
+    /// Where each decision level starts in the trail.
+    trail_starts: Vec<usize>,
+    propagated: usize,
+    activity: Vec<f64>,
+    bump: f64,
+    heap: VarHeap,
+    saved: Vec<bool>,
+    seen: Vec<bool>,
+    conflict_limit: Option<u64>,
+    learnts: usize,
+    max_learnts: usize,
+    unsat: bool,
+}
+
+impl Solver {
+    pub fn new(vars: u32) -> Self {
+        let vars = vars as usize;
+        // Everything starts at the same activity, so any order is a heap
+        let heap = VarHeap {
+            heap: (0..vars).collect(),
+            positions: (0..vars).map(Some).collect(),
+        };
+        Self {
+            clauses: Vec::new(),
+            watches: vec![Vec::new(); 2 * vars],
+            values: vec![None; vars],
+            levels: vec![0; vars],
+            reasons: vec![None; vars],
+            trail: Vec::new(),
+            trail_starts: Vec::new(),
+            propagated: 0,
+            activity: vec![0.0; vars],
+            bump: 1.0,
+            heap,
+            saved: vec![false; vars],
+            seen: vec![false; vars],
+            conflict_limit: None,
+            learnts: 0,
+            max_learnts: 10_000,
+            unsat: false,
+        }
+    }
+
+    /// Give up with [`Outcome::Unknown`] after this many conflicts in one call to
+    /// [`Solver::solve`].
+    pub fn set_conflict_limit(&mut self, limit: Option<u64>) {
+        self.conflict_limit = limit;
+    }
+
+    /// Add a clause of DIMACS literals. This works between calls to [`Solver::solve`] too,
+    /// e.g. to rule out a solution that was already found.
+    pub fn add_clause(&mut self, clause: &[i32]) {
+        if self.unsat {
+            return;
+        }
+        let mut lits = clause
+            .iter()
+            .map(|&lit| lit_from_dimacs(lit))
+            .collect::<Vec<_>>();
+        lits.sort_unstable();
+        lits.dedup();
+        if lits.windows(2).any(|pair| pair[0] ^ 1 == pair[1]) {
+            return;
+        }
+        // Everything's always at level 0 here
+        if lits.iter().any(|&lit| self.value(lit) == Some(true)) {
+            return;
+        }
+        lits.retain(|&lit| self.value(lit).is_none());
+        match lits.len() {
+            0 => self.unsat = true,
+            1 => {
+                self.assign(lits[0], None);
+                if self.propagate().is_some() {
+                    self.unsat = true;
+                }
+            }
+            _ => {
+                self.attach(Clause {
+                    lits,
+                    learnt: false,
+                });
+            }
+        }
+    }
+
+    pub fn solve(&mut self) -> Outcome {
+        if self.unsat {
+            return Outcome::Unsat;
+        }
+        let mut conflicts = 0;
+        let mut restarts = 0;
+        let mut until_restart = 100 * luby(restarts);
+        loop {
+            if let Some(conflict) = self.propagate() {
+                conflicts += 1;
+                if self.trail_starts.is_empty() {
+                    self.unsat = true;
+                    return Outcome::Unsat;
+                }
+                let (learnt, level) = self.analyze(conflict);
+                self.backtrack(level);
+                if learnt.len() == 1 {
+                    self.assign(learnt[0], None);
+                } else {
+                    let asserting = learnt[0];
+                    let idx = self.attach(Clause {
+                        lits: learnt,
+                        learnt: true,
+                    });
+                    self.learnts += 1;
+                    self.assign(asserting, Some(idx));
+                }
+                self.bump /= 0.95;
+
+                if self.conflict_limit.is_some_and(|limit| conflicts >= limit) {
+                    self.backtrack(0);
+                    return Outcome::Unknown;
+                }
+                until_restart -= 1;
+                if until_restart == 0 {
+                    restarts += 1;
+                    until_restart = 100 * luby(restarts);
+                    self.backtrack(0);
+                    if self.learnts > self.max_learnts {
+                        self.reduce();
+                    }
+                }
+            } else {
+                let Some(var) = self.pick() else {
+                    let model = self.values.iter().map(|value| value.unwrap()).collect();
+                    self.backtrack(0);
+                    return Outcome::Sat(model);
+                };
+                self.trail_starts.push(self.trail.len());
+                let lit = 2 * var as u32 + !self.saved[var] as u32;
+                self.assign(lit, None);
+            }
+        }
+    }
+
+    fn value(&self, lit: Lit) -> Option<bool> {
+        self.values[var_of(lit)].map(|value| value != (lit & 1 == 1))
+    }
+
+    fn level(&self) -> usize {
+        self.trail_starts.len()
+    }
+
+    fn assign(&mut self, lit: Lit, reason: Option<usize>) {
+        let var = var_of(lit);
+        self.values[var] = Some(lit & 1 == 0);
+        self.levels[var] = self.level();
+        self.reasons[var] = reason;
+        self.trail.push(lit);
+    }
+
+    fn attach(&mut self, clause: Clause) -> usize {
+        let idx = self.clauses.len();
+        self.watches[clause.lits[0] as usize].push(idx);
+        self.watches[clause.lits[1] as usize].push(idx);
+        self.clauses.push(clause);
+        idx
+    }
+
+    /// Returns a clause that's become false, if there is one.
+    fn propagate(&mut self) -> Option<usize> {
+        while self.propagated < self.trail.len() {
+            let falsified = self.trail[self.propagated] ^ 1;
+            self.propagated += 1;
+
+            let watching = std::mem::take(&mut self.watches[falsified as usize]);
+            let mut kept = Vec::with_capacity(watching.len());
+            let mut conflict = None;
+            for (pos, &idx) in watching.iter().enumerate() {
+                if conflict.is_some() {
+                    kept.extend_from_slice(&watching[pos..]);
+                    break;
+                }
+                let lits = &mut self.clauses[idx].lits;
+                if lits[0] == falsified {
+                    lits.swap(0, 1);
+                }
+                let other = lits[0];
+                if self.values[var_of(other)].is_some_and(|value| value != (other & 1 == 1)) {
+                    kept.push(idx);
+                    continue;
+                }
+
+                let replacement = (2..lits.len()).find(|&k| {
+                    let lit = lits[k];
+                    self.values[var_of(lit)].is_none_or(|value| value != (lit & 1 == 1))
+                });
+                if let Some(k) = replacement {
+                    lits.swap(1, k);
+                    let lit = lits[1];
+                    self.watches[lit as usize].push(idx);
+                    continue;
+                }
+
+                kept.push(idx);
+                match self.value(other) {
+                    Some(false) => conflict = Some(idx),
+                    _ => self.assign(other, Some(idx)),
+                }
+            }
+            self.watches[falsified as usize] = kept;
+            if conflict.is_some() {
+                self.propagated = self.trail.len();
+                return conflict;
+            }
+        }
+        None
+    }
+
+    /// Work out a clause to learn from a conflict and what level to go back to, going
+    /// back to the first point where only one literal from the current level is involved.
+    fn analyze(&mut self, mut conflict: usize) -> (Vec<Lit>, usize) {
+        let mut learnt = vec![0];
+        let mut pending = 0;
+        let mut implied = None;
+        let mut idx = self.trail.len();
+        loop {
+            let skip = implied.is_some() as usize;
+            for k in skip..self.clauses[conflict].lits.len() {
+                let lit = self.clauses[conflict].lits[k];
+                let var = var_of(lit);
+                if self.seen[var] || self.levels[var] == 0 {
+                    continue;
+                }
+                self.seen[var] = true;
+                self.bump_var(var);
+                if self.levels[var] == self.level() {
+                    pending += 1;
+                } else {
+                    learnt.push(lit);
+                }
+            }
+
+            loop {
+                idx -= 1;
+                if self.seen[var_of(self.trail[idx])] {
+                    break;
+                }
+            }
+            let lit = self.trail[idx];
+            self.seen[var_of(lit)] = false;
+            pending -= 1;
+            if pending == 0 {
+                learnt[0] = lit ^ 1;
+                break;
+            }
+            implied = Some(lit);
+            conflict = self.reasons[var_of(lit)].expect("only decisions have no reason");
+        }
+        for &lit in &learnt[1..] {
+            self.seen[var_of(lit)] = false;
+        }
+
+        // Watch whatever's at the highest level after the asserting literal, so the
+        // clause is watched properly after going back
+        let mut level = 0;
+        if learnt.len() > 1 {
+            let highest = (1..learnt.len())
+                .max_by_key(|&k| self.levels[var_of(learnt[k])])
+                .unwrap();
+            learnt.swap(1, highest);
+            level = self.levels[var_of(learnt[1])];
+        }
+        (learnt, level)
+    }
+
+    fn bump_var(&mut self, var: usize) {
+        self.activity[var] += self.bump;
+        if self.activity[var] > 1e100 {
+            for activity in self.activity.iter_mut() {
+                *activity *= 1e-100;
+            }
+            self.bump *= 1e-100;
+        }
+        self.heap.increased(var, &self.activity);
+    }
+
+    fn backtrack(&mut self, level: usize) {
+        if self.level() <= level {
+            return;
+        }
+        let start = self.trail_starts[level];
+        for lit in self.trail.drain(start..) {
+            let var = var_of(lit);
+            self.saved[var] = lit & 1 == 0;
+            self.values[var] = None;
+            self.reasons[var] = None;
+            if !self.heap.contains(var) {
+                self.heap.insert(var, &self.activity);
+            }
+        }
+        self.trail_starts.truncate(level);
+        self.propagated = self.trail.len();
+    }
+
+    fn pick(&mut self) -> Option<usize> {
+        while let Some(var) = self.heap.pop(&self.activity) {
+            if self.values[var].is_none() {
+                return Some(var);
+            }
+        }
+        None
+    }
+
+    /// Throw away the longer half of the learnt clauses. Only done at level 0, where
+    /// nothing needs the reasons any more.
+    fn reduce(&mut self) {
+        let mut lengths = self
+            .clauses
+            .iter()
+            .filter(|clause| clause.learnt)
+            .map(|clause| clause.lits.len())
+            .collect::<Vec<_>>();
+        lengths.sort_unstable();
+        let cutoff = lengths[lengths.len() / 2];
+        self.clauses
+            .retain(|clause| !clause.learnt || clause.lits.len() < cutoff);
+        self.learnts = self.clauses.iter().filter(|clause| clause.learnt).count();
+        self.max_learnts += self.max_learnts / 2;
+
+        for reason in self.reasons.iter_mut() {
+            *reason = None;
+        }
+        for watching in self.watches.iter_mut() {
+            watching.clear();
+        }
+        for (idx, clause) in self.clauses.iter().enumerate() {
+            self.watches[clause.lits[0] as usize].push(idx);
+            self.watches[clause.lits[1] as usize].push(idx);
+        }
+    }
+}
+
+/// 1, 1, 2, 1, 1, 2, 4, 1, 1, 2, ...
+fn luby(mut idx: u64) -> u64 {
+    let (mut size, mut power) = (1, 0);
+    while size < idx + 1 {
+        power += 1;
+        size = 2 * size + 1;
+    }
+    while size - 1 != idx {
+        size = (size - 1) >> 1;
+        power -= 1;
+        idx %= size;
+    }
+    1 << power
+}
+
+/// Unassigned variables by activity, most active on top.
+struct VarHeap {
+    heap: Vec<usize>,
+    /// Where each variable is in the heap.
+    positions: Vec<Option<usize>>,
+}
+
+impl VarHeap {
+    fn contains(&self, var: usize) -> bool {
+        self.positions[var].is_some()
+    }
+
+    fn insert(&mut self, var: usize, activity: &[f64]) {
+        self.positions[var] = Some(self.heap.len());
+        self.heap.push(var);
+        self.up(self.heap.len() - 1, activity);
+    }
+
+    fn increased(&mut self, var: usize, activity: &[f64]) {
+        if let Some(pos) = self.positions[var] {
+            self.up(pos, activity);
+        }
+    }
+
+    fn pop(&mut self, activity: &[f64]) -> Option<usize> {
+        let top = *self.heap.first()?;
+        let last = self.heap.pop().unwrap();
+        self.positions[top] = None;
+        if !self.heap.is_empty() {
+            self.heap[0] = last;
+            self.positions[last] = Some(0);
+            self.down(0, activity);
+        }
+        Some(top)
+    }
+
+    fn up(&mut self, mut pos: usize, activity: &[f64]) {
+        while pos > 0 {
+            let parent = (pos - 1) / 2;
+            if activity[self.heap[parent]] >= activity[self.heap[pos]] {
+                break;
+            }
+            self.swap(pos, parent);
+            pos = parent;
+        }
+    }
+
+    fn down(&mut self, mut pos: usize, activity: &[f64]) {
+        loop {
+            let mut biggest = pos;
+            for child in [2 * pos + 1, 2 * pos + 2] {
+                if child < self.heap.len()
+                    && activity[self.heap[child]] > activity[self.heap[biggest]]
+                {
+                    biggest = child;
+                }
+            }
+            if biggest == pos {
+                break;
+            }
+            self.swap(pos, biggest);
+            pos = biggest;
+        }
+    }
+
+    fn swap(&mut self, a: usize, b: usize) {
+        self.heap.swap(a, b);
+        self.positions[self.heap[a]] = Some(a);
+        self.positions[self.heap[b]] = Some(b);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{random::Rng, sat::Cnf};
+
+    fn satisfies(cnf: &Cnf, model: &[bool]) -> bool {
+        cnf.clauses.iter().all(|clause| {
+            clause
+                .iter()
+                .any(|&lit| model[lit.unsigned_abs() as usize - 1] == (lit > 0))
+        })
+    }
+
+    /// Every assignment that satisfies it.
+    fn brute_force(cnf: &Cnf) -> Vec<Vec<bool>> {
+        (0..1u32 << cnf.vars)
+            .map(|bits| (0..cnf.vars).map(|var| bits >> var & 1 == 1).collect())
+            .filter(|model: &Vec<bool>| satisfies(cnf, model))
+            .collect()
+    }
+
+    /// `pigeons` pigeons in `holes` holes, no two sharing.
+    fn pigeonhole(pigeons: u32, holes: u32) -> Cnf {
+        let mut cnf = Cnf::new();
+        cnf.vars = pigeons * holes;
+        let var = |pigeon: u32, hole: u32| (pigeon * holes + hole + 1) as i32;
+        for pigeon in 0..pigeons {
+            cnf.add((0..holes).map(|hole| var(pigeon, hole)));
+        }
+        for hole in 0..holes {
+            for a in 0..pigeons {
+                for b in a + 1..pigeons {
+                    cnf.add([-var(a, hole), -var(b, hole)]);
+                }
+            }
+        }
+        cnf
+    }
+
+    #[test]
+    fn known_instances() {
+        let mut cnf = Cnf::new();
+        cnf.vars = 3;
+        cnf.add([1, 2]);
+        cnf.add([-1, 3]);
+        cnf.add([-2, -3]);
+        cnf.add([-3, 1]);
+        cnf.add([1]);
+        assert_eq!(cnf.solve(), Outcome::Sat(vec![true, false, true]));
+
+        cnf.add([-1, 2]);
+        assert_eq!(cnf.solve(), Outcome::Unsat);
+
+        assert!(matches!(pigeonhole(5, 5).solve(), Outcome::Sat(_)));
+        assert_eq!(pigeonhole(6, 5).solve(), Outcome::Unsat);
+
+        let mut solver = pigeonhole(8, 7).solver();
+        solver.set_conflict_limit(Some(1));
+        assert_eq!(solver.solve(), Outcome::Unknown);
+    }
+
+    #[test]
+    fn matches_brute_force() {
+        let mut rng = Rng::new(0);
+        for _ in 0..500 {
+            let mut cnf = Cnf::new();
+            cnf.vars = 1 + rng.below(10) as u32;
+            for _ in 0..rng.below(5 * cnf.vars as u64) {
+                let len = 1 + rng.below(4);
+                let clause = (0..len)
+                    .map(|_| {
+                        let var = 1 + rng.below(cnf.vars as u64) as i32;
+                        if rng.chance(0.5) {
+                            var
+                        } else {
+                            -var
+                        }
+                    })
+                    .collect::<Vec<_>>();
+                cnf.add(clause);
+            }
+            let models = brute_force(&cnf);
+
+            // Find them all, ruling out each one as it's found
+            let mut solver = cnf.solver();
+            let mut found = 0;
+            while let Outcome::Sat(model) = solver.solve() {
+                assert!(satisfies(&cnf, &model), "{:?}", cnf);
+                found += 1;
+                assert!(found <= models.len(), "{:?}", cnf);
+                let block = model
+                    .iter()
+                    .enumerate()
+                    .map(|(var, &value)| {
+                        if value {
+                            -(var as i32 + 1)
+                        } else {
+                            var as i32 + 1
+                        }
+                    })
+                    .collect::<Vec<_>>();
+                solver.add_clause(&block);
+            }
+            assert_eq!(found, models.len(), "{:?}", cnf);
+        }
+    }
+}
@@ -0,0 +1,137 @@
+//! Filling regions with random junk.
+
+use ahash::AHashSet;
+
+use crate::{
+    math::{Aliveness, EdgePos, RestrictedHexDir},
+    random::Rng,
+    region::Region,
+    symmetry::Transform,
+    Board,
+};
+
+/// How likely each edge is to start out in each state. Whatever's left over is dead.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Density {
+    pub alive: f64,
+    pub barren: f64,
+}
+
+impl Density {
+    /// Only alive edges, no barren ones.
+    pub fn alive(alive: f64) -> Self {
+        Self { alive, barren: 0.0 }
+    }
+
+    fn roll(&self, rng: &mut Rng) -> Aliveness {
+        let roll = rng.next_f64();
+        if roll < self.alive {
+            Aliveness::Alive
+        } else if roll < self.alive + self.barren {
+            Aliveness::Barren
+        } else {
+            Aliveness::Dead
+        }
+    }
+}
+
+impl Default for Density {
+    fn default() -> Self {
+        Self::alive(0.5)
+    }
+}
+
+/// Which symmetry to force a soup to have, around the middle of its region.
+///
+/// The `D` ones include reflections; their number is how many transforms there are in
+/// total.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum SoupSymmetry {
+    #[default]
+    None,
+    /// Half-turn.
+    C2,
+    /// Third-turns.
+    C3,
+    /// Sixth-turns.
+    C6,
+    /// One reflection.
+    D2,
+    /// Half-turn and two reflections.
+    D4,
+    /// Third-turns and three reflections.
+    D6,
+    /// Everything.
+    D12,
+}
+
+impl SoupSymmetry {
+    /// Every transform in the group, around the origin.
+    pub fn transforms(&self) -> Vec<Transform> {
+        let (step, mirrored) = match self {
+            SoupSymmetry::None => (6, false),
+            SoupSymmetry::C2 => (3, false),
+            SoupSymmetry::C3 => (2, false),
+            SoupSymmetry::C6 => (1, false),
+            SoupSymmetry::D2 => (6, true),
+            SoupSymmetry::D4 => (3, true),
+            SoupSymmetry::D6 => (2, true),
+            SoupSymmetry::D12 => (1, true),
+        };
+        let rotations = (0..6).step_by(step).map(Transform::rotate);
+        if mirrored {
+            rotations
+                .clone()
+                .chain(rotations.map(|rotation| Transform::mirror().then(rotation)))
+                .collect()
+        } else {
+            rotations.collect()
+        }
+    }
+}
+
+impl Board {
+    /// Randomly set every edge stored on a hex in the region, dead ones included.
+    ///
+    /// With a symmetry, every edge the symmetry maps a filled edge to gets the same state,
+    /// even if it lands outside the region. The same seed always gives the same soup.
+    pub fn random_fill(
+        &mut self,
+        region: &Region,
+        density: Density,
+        symmetry: SoupSymmetry,
+        seed: u64,
+    ) {
+        let mut rng = Rng::new(seed);
+        let center = region.center();
+        let transforms = symmetry
+            .transforms()
+            .into_iter()
+            .map(|t| {
+                Transform::translate(-center)
+                    .then(t)
+                    .then(Transform::translate(center))
+            })
+            .collect::<Vec<_>>();
+
+        // Region coords come out in hash order, which changes from run to run
+        let mut coords = region.coords();
+        coords.sort_unstable();
+
+        let mut filled = AHashSet::new();
+        for coord in coords {
+            for edge in RestrictedHexDir::all() {
+                let pos = EdgePos::new_raw(coord, edge);
+                if filled.contains(&pos) {
+                    continue;
+                }
+                let alive = density.roll(&mut rng);
+                for transform in transforms.iter() {
+                    let image = transform.apply_edge(pos);
+                    filled.insert(image);
+                    self.set_alive(image, alive);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,266 @@
+//! Numbers that sum up what a board looks like, for plotting over time.
+//!
+//! Everything is measured over a [`Region`], since the board itself goes on forever.
+//! Each hex in the region counts with the three edges stored on it.
+
+use ahash::AHashMap;
+
+use crate::{
+    math::{Aliveness, EdgePos, RestrictedHexDir},
+    region::Region,
+    Board,
+};
+
+/// How much of one orientation of edge is in each state.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OrientationDensity {
+    pub edge: RestrictedHexDir,
+    /// Fraction of these edges that are alive.
+    pub alive: f64,
+    /// Fraction of these edges that are barren.
+    pub barren: f64,
+}
+
+/// Alive and barren densities for each of the three orientations, in the same order as
+/// [`RestrictedHexDir::all`].
+pub fn densities(board: &Board, region: &Region) -> [OrientationDensity; 3] {
+    let mut counts = [(0, 0); 3];
+    for coord in region.coords() {
+        for (idx, &edge) in RestrictedHexDir::all().iter().enumerate() {
+            match board.get_liveness(EdgePos::new_raw(coord, edge)) {
+                Aliveness::Alive => counts[idx].0 += 1,
+                Aliveness::Barren => counts[idx].1 += 1,
+                Aliveness::Dead => {}
+            }
+        }
+    }
+    let hexes = region.len().max(1) as f64;
+    std::array::from_fn(|idx| {
+        let (alive, barren) = counts[idx];
+        OrientationDensity {
+            edge: RestrictedHexDir::all()[idx],
+            alive: alive as f64 / hexes,
+            barren: barren as f64 / hexes,
+        }
+    })
+}
+
+/// How unpredictable the board looks through a window of a given size.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BlockEntropy {
+    /// Blocks are every hex at most this many steps from one in the region.
+    pub radius: i64,
+    /// How many different blocks turned up.
+    pub distinct: usize,
+    /// Shannon entropy of which block turns up, in bits.
+    pub entropy: f64,
+    /// The same, divided by the number of edges in a block.
+    pub per_edge: f64,
+}
+
+/// Look at the block of edges around every hex in the region, and work out the entropy
+/// of what's in them.
+///
+/// Radius 0 blocks are just the three edges on one hex.
+pub fn block_entropy(board: &Board, region: &Region, radius: i64) -> BlockEntropy {
+    let radius = radius.max(0);
+    let mut counts = AHashMap::<Vec<u8>, usize>::new();
+    let coords = region.coords();
+    for &center in coords.iter() {
+        let block = center
+            .range_iter(radius)
+            .map(|coord| board.get_edges(coord).map_or(0, |edges| edges.pack()))
+            .collect::<Vec<_>>();
+        *counts.entry(block).or_default() += 1;
+    }
+
+    let total = coords.len().max(1) as f64;
+    let entropy = counts
+        .values()
+        .map(|&count| {
+            let p = count as f64 / total;
+            -p * p.log2()
+        })
+        .sum::<f64>();
+    let edges = 3 * (3 * radius * (radius + 1) + 1);
+    BlockEntropy {
+        radius,
+        distinct: counts.len(),
+        entropy,
+        per_edge: entropy / edges as f64,
+    }
+}
+
+/// How much whether one edge is alive says about whether another is, for edges a certain
+/// distance apart.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Correlation {
+    /// Steps between the hexes the edges are stored on.
+    pub distance: i64,
+    /// How many pairs of edges were that far apart, counting each pair once each way.
+    pub pairs: usize,
+    /// Pearson correlation of the two edges being alive, from -1 to 1. 0 if every edge
+    /// was the same.
+    pub correlation: f64,
+}
+
+/// Correlation of edges being alive, for every distance from 0 up to `max_distance`.
+/// Distance 0 is between different edges on the same hex.
+pub fn correlations(board: &Board, region: &Region, max_distance: i64) -> Vec<Correlation> {
+    let max_distance = max_distance.max(0);
+    let alive = |pos| board.get_liveness(pos) == Aliveness::Alive;
+    // Pairs, pairs where one is alive, and pairs where both are
+    let mut sums = vec![(0usize, 0usize, 0usize); max_distance as usize + 1];
+    for coord in region.coords() {
+        for edge in RestrictedHexDir::all() {
+            let pos = EdgePos::new_raw(coord, edge);
+            let here = alive(pos);
+            for other in coord.range_iter(max_distance) {
+                if !region.contains(other) {
+                    continue;
+                }
+                let sums = &mut sums[coord.distance(other) as usize];
+                for other_edge in RestrictedHexDir::all() {
+                    let other = EdgePos::new_raw(other, other_edge);
+                    if other == pos {
+                        continue;
+                    }
+                    sums.0 += 1;
+                    if here {
+                        sums.1 += 1;
+                        if alive(other) {
+                            sums.2 += 1;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    sums.into_iter()
+        .enumerate()
+        .map(|(distance, (pairs, one, both))| {
+            // Every pair's counted both ways round, so both ends have the same mean
+            let mean = one as f64 / pairs.max(1) as f64;
+            let variance = mean * (1.0 - mean);
+            let correlation = if variance > 0.0 {
+                (both as f64 / pairs as f64 - mean * mean) / variance
+            } else {
+                0.0
+            };
+            Correlation {
+                distance: distance as i64,
+                pairs,
+                correlation,
+            }
+        })
+        .collect()
+}
+
+/// How much changed from one board to the next.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Activity {
+    /// Edges in the region that are in a different state.
+    pub changed: usize,
+    /// Fraction of all the edges in the region that changed.
+    pub fraction: f64,
+}
+
+/// Compare two boards, usually one generation and the next.
+pub fn activity(before: &Board, after: &Board, region: &Region) -> Activity {
+    let changed = before
+        .diff(after)
+        .iter()
+        .filter(|change| region.contains(change.pos.coord()))
+        .count();
+    Activity {
+        changed,
+        fraction: changed as f64 / (3 * region.len()).max(1) as f64,
+    }
+}
+
+/// What [`statistics`] measures.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StatisticsConfig {
+    /// Radius of the blocks for [`block_entropy`].
+    pub block_radius: i64,
+    /// Furthest distance for [`correlations`].
+    pub max_distance: i64,
+}
+
+impl Default for StatisticsConfig {
+    fn default() -> Self {
+        Self {
+            block_radius: 1,
+            max_distance: 8,
+        }
+    }
+}
+
+/// Everything measured about one board.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Statistics {
+    pub densities: [OrientationDensity; 3],
+    pub entropy: BlockEntropy,
+    pub correlations: Vec<Correlation>,
+}
+
+pub fn statistics(board: &Board, region: &Region, config: StatisticsConfig) -> Statistics {
+    Statistics {
+        densities: densities(board, region),
+        entropy: block_entropy(board, region, config.block_radius),
+        correlations: correlations(board, region, config.max_distance),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::HexCoord;
+
+    /// A 2x2 block of hexes, each with its right edge alive, and one with its down-right
+    /// edge barren too.
+    fn striped() -> (Board, Region) {
+        let region = Region::between(HexCoord::new(0, 0), HexCoord::new(1, 1));
+        let mut board = Board::new();
+        for coord in region.coords() {
+            board.set_alive(
+                EdgePos::new_raw(coord, RestrictedHexDir::XY),
+                Aliveness::Alive,
+            );
+        }
+        let corner = EdgePos::new_raw(HexCoord::new(0, 0), RestrictedHexDir::ZY);
+        board.set_alive(corner, Aliveness::Barren);
+        (board, region)
+    }
+
+    #[test]
+    fn measures_a_known_board() {
+        let (board, region) = striped();
+        let [xy, zy, zx] = densities(&board, &region);
+        assert_eq!((xy.alive, xy.barren), (1.0, 0.0));
+        assert_eq!((zy.alive, zy.barren), (0.0, 0.25));
+        assert_eq!((zx.alive, zx.barren), (0.0, 0.0));
+
+        // Three hexes look one way and one looks another
+        let entropy = block_entropy(&board, &region, 0);
+        assert_eq!(entropy.distinct, 2);
+        let expected = -(0.75f64 * 0.75f64.log2() + 0.25 * 0.25f64.log2());
+        assert!((entropy.entropy - expected).abs() < 1e-9);
+        assert!((entropy.per_edge - expected / 3.0).abs() < 1e-9);
+
+        // On the same hex, one edge being alive means the other two aren't
+        let same_hex = correlations(&board, &region, 0)[0];
+        assert_eq!(same_hex.pairs, 4 * 3 * 2);
+        assert!((same_hex.correlation + 0.5).abs() < 1e-9);
+
+        let mut after = board.clone();
+        after.set_alive(
+            EdgePos::new_raw(HexCoord::new(1, 1), RestrictedHexDir::XY),
+            Aliveness::Dead,
+        );
+        let activity = activity(&board, &after, &region);
+        assert_eq!(activity.changed, 1);
+        assert_eq!(activity.fraction, 1.0 / 12.0);
+    }
+}
@@ -0,0 +1,335 @@
+//! Noticing when a board has stopped doing anything interesting.
+//!
+//! Every generation the board gets fingerprinted, and the fingerprints of recent generations
+//! are remembered. A fingerprint showing up again means the board is probably repeating
+//! itself, possibly somewhere else. Different boards can have the same fingerprint, so
+//! before believing it, a copy of the board gets run forward to check it really does come
+//! back.
+
+use std::collections::VecDeque;
+
+use ahash::AHashMap;
+
+use crate::{math::HexCoord, symmetry::Transform, transition::TransitionTable, Board, Fingerprint};
+
+/// What the board has settled into, if anything.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Outcome {
+    /// Still changing, as far as we can tell.
+    #[default]
+    Running,
+    /// Nothing is left, and hasn't been since `gen`.
+    Extinct { gen: u64 },
+    /// The board has been the same since `gen`.
+    Stable { gen: u64 },
+    /// The board has been repeating every `period` generations since `first_gen`.
+    Periodic { period: u64, first_gen: u64 },
+    /// The board has been repeating every `period` generations since `first_gen`, but moved
+    /// by `displacement` (in axial coordinates) each time.
+    Moving {
+        period: u64,
+        displacement: HexCoord,
+        first_gen: u64,
+    },
+}
+
+impl Outcome {
+    /// Is this anything but `Running`?
+    pub fn is_settled(&self) -> bool {
+        !matches!(self, Outcome::Running)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StopConfig {
+    /// Longest period to look for.
+    pub max_period: u64,
+    /// Most memory to spend remembering fingerprints, in bytes. Periods longer than fit
+    /// won't be found.
+    pub max_bytes: usize,
+}
+
+impl Default for StopConfig {
+    fn default() -> Self {
+        Self {
+            max_period: 1024,
+            max_bytes: 1 << 20,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct StopDetector {
+    config: StopConfig,
+    /// How boards get fingerprinted.
+    fingerprint: fn(&Board) -> Fingerprint,
+    /// Maps fingerprint hashes to the generations they were seen, oldest first.
+    seen: AHashMap<u64, VecDeque<u64>>,
+    /// Every generation still remembered, oldest first.
+    history: VecDeque<State>,
+    /// What the last observation found, so the start of it can be kept track of.
+    last: Outcome,
+}
+
+/// One generation, remembered.
+#[derive(Debug, Clone, Copy)]
+struct State {
+    generation: u64,
+    hash: u64,
+    offset: HexCoord,
+}
+
+/// What remembering one generation costs.
+const STATE_BYTES: usize = std::mem::size_of::<State>() + std::mem::size_of::<u64>();
+
+impl Default for StopDetector {
+    fn default() -> Self {
+        Self::new(StopConfig::default())
+    }
+}
+
+impl StopDetector {
+    pub fn new(config: StopConfig) -> Self {
+        Self::with_fingerprint(config, Board::fingerprint)
+    }
+
+    /// Fingerprint boards some other way than [`Board::fingerprint`].
+    pub fn with_fingerprint(config: StopConfig, fingerprint: fn(&Board) -> Fingerprint) -> Self {
+        Self {
+            config,
+            fingerprint,
+            seen: AHashMap::new(),
+            history: VecDeque::new(),
+            last: Outcome::Running,
+        }
+    }
+
+    pub fn config(&self) -> StopConfig {
+        self.config
+    }
+
+    /// Forget everything seen so far.
+    pub fn reset(&mut self) {
+        self.seen.clear();
+        self.history.clear();
+        self.last = Outcome::Running;
+    }
+
+    /// Look at the board as of the given generation, which got there by stepping with
+    /// `table`.
+    ///
+    /// This should be called once per generation, in order.
+    pub fn observe(&mut self, board: &Board, generation: u64, table: &TransitionTable) -> Outcome {
+        let Fingerprint { hash, offset } = (self.fingerprint)(board);
+
+        let outcome = if board.is_empty() {
+            match self.last {
+                Outcome::Extinct { gen } => Outcome::Extinct { gen },
+                _ => Outcome::Extinct { gen: generation },
+            }
+        } else {
+            match self.previous(board, hash, offset, generation, table) {
+                Some((prev, prev_offset)) => {
+                    let period = generation - prev;
+                    let displacement = offset - prev_offset;
+                    // If it was already doing this, it started back then
+                    let first_gen = match self.last {
+                        Outcome::Stable { gen }
+                            if period == 1 && displacement == HexCoord::new(0, 0) =>
+                        {
+                            gen
+                        }
+                        Outcome::Periodic {
+                            period: last_period,
+                            first_gen,
+                        } if last_period == period && displacement == HexCoord::new(0, 0) => {
+                            first_gen
+                        }
+                        Outcome::Moving {
+                            period: last_period,
+                            displacement: last_displacement,
+                            first_gen,
+                        } if last_period == period && last_displacement == displacement => {
+                            first_gen
+                        }
+                        _ => prev,
+                    };
+                    if displacement != HexCoord::new(0, 0) {
+                        Outcome::Moving {
+                            period,
+                            displacement,
+                            first_gen,
+                        }
+                    } else if period == 1 {
+                        Outcome::Stable { gen: first_gen }
+                    } else {
+                        Outcome::Periodic { period, first_gen }
+                    }
+                }
+                None => Outcome::Running,
+            }
+        };
+
+        self.seen.entry(hash).or_default().push_back(generation);
+        self.history.push_back(State {
+            generation,
+            hash,
+            offset,
+        });
+        while let Some(old) = self.history.front() {
+            let too_old = generation - old.generation > self.config.max_period;
+            if !too_old && self.history.len() * STATE_BYTES <= self.config.max_bytes {
+                break;
+            }
+            let old = self.history.pop_front().expect("just looked at it");
+            if let Some(gens) = self.seen.get_mut(&old.hash) {
+                gens.pop_front();
+                if gens.is_empty() {
+                    self.seen.remove(&old.hash);
+                }
+            }
+        }
+
+        self.last = outcome;
+        outcome
+    }
+
+    /// The last generation within range that the board really is the same as, and its
+    /// offset.
+    ///
+    /// The board really does repeat, but that it was already doing it back then is only
+    /// going by the fingerprint.
+    fn previous(
+        &self,
+        board: &Board,
+        hash: u64,
+        offset: HexCoord,
+        generation: u64,
+        table: &TransitionTable,
+    ) -> Option<(u64, HexCoord)> {
+        let gens = self.seen.get(&hash)?;
+        for &prev in gens.iter().rev() {
+            let period = generation - prev;
+            if period > self.config.max_period {
+                break;
+            }
+            let Ok(idx) = self
+                .history
+                .binary_search_by_key(&prev, |state| state.generation)
+            else {
+                continue;
+            };
+            let prev_offset = self.history[idx].offset;
+            let displacement = offset - prev_offset;
+            // Once it's really repeating it keeps on doing it, so there's no need to check again
+            let already = match self.last {
+                Outcome::Stable { .. } => period == 1 && displacement == HexCoord::new(0, 0),
+                Outcome::Periodic {
+                    period: last_period,
+                    ..
+                } => last_period == period && displacement == HexCoord::new(0, 0),
+                Outcome::Moving {
+                    period: last_period,
+                    displacement: last_displacement,
+                    ..
+                } => last_period == period && last_displacement == displacement,
+                _ => false,
+            };
+            if already || comes_back(board, period, displacement, table) {
+                return Some((prev, prev_offset));
+            }
+        }
+        None
+    }
+}
+
+/// Whether the board is the same after `period` generations, moved by `displacement`.
+fn comes_back(board: &Board, period: u64, displacement: HexCoord, table: &TransitionTable) -> bool {
+    let mut later = board.clone();
+    for _ in 0..period {
+        later.apply_table(table);
+    }
+    later == board.transformed(Transform::translate(displacement))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        code,
+        region::Region,
+        soup::{Density, SoupSymmetry},
+        Rule,
+    };
+
+    /// Every board looks the same.
+    fn colliding(board: &Board) -> Fingerprint {
+        Fingerprint {
+            hash: 0,
+            ..board.fingerprint()
+        }
+    }
+
+    #[test]
+    fn hash_collisions_arent_repeats() {
+        let rule: Rule = "B2/S3/@8*".parse().unwrap();
+        let table = rule.compile();
+        let mut board = code::decode("xq2_069z1a6z66").unwrap().1.to_board();
+        let mut detector = StopDetector::with_fingerprint(StopConfig::default(), colliding);
+        assert_eq!(detector.observe(&board, 0, &table), Outcome::Running);
+        board.apply_table(&table);
+        assert_eq!(detector.observe(&board, 1, &table), Outcome::Running);
+        board.apply_table(&table);
+        assert_eq!(
+            detector.observe(&board, 2, &table),
+            Outcome::Moving {
+                period: 2,
+                displacement: HexCoord::new(0, 1),
+                first_gen: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn collisions_only_ever_find_real_repeats() {
+        let config = StopConfig {
+            max_period: 16,
+            ..Default::default()
+        };
+        let rules = ["B346/S046/@6", "B2/S3/@8*", "B3456/S34/@6"];
+        let mut found = 0;
+        for (seed, rule) in rules.into_iter().enumerate() {
+            let rule: Rule = rule.parse().unwrap();
+            let table = rule.compile();
+            let density = Density {
+                alive: 0.4,
+                barren: 0.1,
+            };
+            let region = Region::between(HexCoord::new(0, 0), HexCoord::new(5, 5));
+            let mut board = Board::new();
+            board.random_fill(&region, density, SoupSymmetry::None, seed as u64);
+            let mut detector = StopDetector::with_fingerprint(config, colliding);
+            for generation in 0..60 {
+                let (period, displacement) = match detector.observe(&board, generation, &table) {
+                    Outcome::Stable { .. } => (1, HexCoord::new(0, 0)),
+                    Outcome::Periodic { period, .. } => (period, HexCoord::new(0, 0)),
+                    Outcome::Moving {
+                        period,
+                        displacement,
+                        ..
+                    } => (period, displacement),
+                    _ => (0, HexCoord::new(0, 0)),
+                };
+                if period > 0 {
+                    found += 1;
+                    assert!(
+                        comes_back(&board, period, displacement, &table),
+                        "{rule} seed {seed} generation {generation}"
+                    );
+                }
+                board.apply_table(&table);
+            }
+        }
+        assert!(found > 0);
+    }
+}
@@ -0,0 +1,175 @@
+//! Rotating, mirroring and moving patterns around.
+//!
+//! The hex grid has 12 symmetries that keep the origin where it is (6 rotations, and 6
+//! reflections), which together with translations are everything a [`Transform`] can be.
+//! Edges get transformed by transforming the two hexes they sit between.
+
+use hex2d::Direction;
+
+use crate::{
+    math::{EdgePos, HexCoord},
+    Board,
+};
+
+/// A symmetry of the hex grid, followed by a translation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Transform {
+    /// Linear part acting on axial coordinates, row-major.
+    matrix: [i64; 4],
+    translation: HexCoord,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+impl Transform {
+    pub fn identity() -> Self {
+        Self {
+            matrix: [1, 0, 0, 1],
+            translation: HexCoord::new(0, 0),
+        }
+    }
+
+    /// Rotate `steps` sixths of a turn clockwise around the origin.
+    pub fn rotate(steps: i32) -> Self {
+        // One step takes (x, y, z) to (-z, -x, -y), just like hex2d does it.
+        let one = [1, 1, -1, 0];
+        let mut matrix = [1, 0, 0, 1];
+        for _ in 0..steps.rem_euclid(6) {
+            matrix = mul(one, matrix);
+        }
+        Self {
+            matrix,
+            translation: HexCoord::new(0, 0),
+        }
+    }
+
+    /// Mirror across the line through the origin where x = y.
+    pub fn mirror() -> Self {
+        Self {
+            matrix: [0, 1, 1, 0],
+            translation: HexCoord::new(0, 0),
+        }
+    }
+
+    pub fn translate(by: HexCoord) -> Self {
+        Self {
+            matrix: [1, 0, 0, 1],
+            translation: by,
+        }
+    }
+
+    /// All 12 symmetries that keep the origin in place: the rotations, and then the
+    /// mirrored rotations.
+    pub fn symmetries() -> [Transform; 12] {
+        let mut out = [Transform::identity(); 12];
+        for steps in 0..6 {
+            out[steps] = Transform::rotate(steps as i32);
+            out[steps + 6] = Transform::mirror().then(Transform::rotate(steps as i32));
+        }
+        out
+    }
+
+    /// Do this, and then do the other one.
+    pub fn then(&self, other: Transform) -> Transform {
+        Transform {
+            matrix: mul(other.matrix, self.matrix),
+            translation: other.apply_linear(self.translation) + other.translation,
+        }
+    }
+
+    pub fn inverse(&self) -> Transform {
+        let [a, b, c, d] = self.matrix;
+        // These are all determinant 1 or -1
+        let det = a * d - b * c;
+        let matrix = [d * det, -b * det, -c * det, a * det];
+        let linear = Transform {
+            matrix,
+            translation: HexCoord::new(0, 0),
+        };
+        Transform {
+            matrix,
+            translation: -linear.apply_linear(self.translation),
+        }
+    }
+
+    /// Does this flip things over?
+    pub fn is_mirrored(&self) -> bool {
+        let [a, b, c, d] = self.matrix;
+        a * d - b * c < 0
+    }
+
+    pub fn translation(&self) -> HexCoord {
+        self.translation
+    }
+
+    /// The same transform without the translation.
+    pub fn linear(&self) -> Transform {
+        Transform {
+            matrix: self.matrix,
+            translation: HexCoord::new(0, 0),
+        }
+    }
+
+    pub fn apply_coord(&self, coord: HexCoord) -> HexCoord {
+        self.apply_linear(coord) + self.translation
+    }
+
+    pub fn apply_edge(&self, pos: EdgePos) -> EdgePos {
+        let from = self.apply_coord(pos.coord());
+        let to = self.apply_coord(pos.coord() + pos.dir());
+        let dir = Direction::all()
+            .iter()
+            .copied()
+            .find(|&dir| from + dir == to)
+            .expect("transforms should keep neighbors next to each other");
+        EdgePos::new(from, dir)
+    }
+
+    fn apply_linear(&self, coord: HexCoord) -> HexCoord {
+        let [a, b, c, d] = self.matrix;
+        HexCoord::new(a * coord.x + b * coord.y, c * coord.x + d * coord.y)
+    }
+}
+
+fn mul(lhs: [i64; 4], rhs: [i64; 4]) -> [i64; 4] {
+    let [a, b, c, d] = lhs;
+    let [e, f, g, h] = rhs;
+    [a * e + b * g, a * f + b * h, c * e + d * g, c * f + d * h]
+}
+
+impl Board {
+    /// Make a copy of the board with everything on it transformed.
+    pub fn transformed(&self, transform: Transform) -> Board {
+        let mut out = Board::new();
+        for (pos, alive) in self.edges() {
+            out.set_alive(transform.apply_edge(pos), alive);
+        }
+        out
+    }
+
+    /// Get the same board for every rotation, reflection and translation of this one.
+    ///
+    /// Returns the canonical board and the transform that turns this board into it. The
+    /// smallest coordinate with anything stored at it is always at the origin.
+    pub fn canonical(&self) -> (Board, Transform) {
+        let (_, transform) = Transform::symmetries()
+            .into_iter()
+            .map(|symmetry| {
+                let turned = self.transformed(symmetry);
+                let offset = turned.fingerprint().offset;
+                let mut key = turned.sorted_cells();
+                for (x, y, _) in key.iter_mut() {
+                    *x -= offset.x;
+                    *y -= offset.y;
+                }
+                (key, symmetry.then(Transform::translate(-offset)))
+            })
+            .min_by(|(lhs, _), (rhs, _)| lhs.cmp(rhs))
+            .expect("there are 12 symmetries");
+        (self.transformed(transform), transform)
+    }
+}
@@ -0,0 +1,350 @@
+//! Following objects from one generation to the next.
+//!
+//! Every generation the board gets split into [components](Board::components). A new
+//! component counts as coming from an old one if any of its edges are, or are
+//! neighbors of, that old component's live edges, since that's the only way the old one
+//! could have had anything to do with it. Each old object hands its ID to the new
+//! component it overlaps the most, so objects keep their IDs for as long as they stay in
+//! one piece, and the rest get new IDs.
+//!
+//! Several old objects whose biggest overlap is the same new component merged into it,
+//! and several new components whose biggest overlap is the same old object split off
+//! from it.
+
+use ahash::AHashMap;
+
+use crate::{
+    components::{Adjacency, Component},
+    math::Aliveness,
+    Board, NeighborRegion, Rule,
+};
+
+pub type ObjectId = u64;
+
+/// One object being followed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrackedObject {
+    pub id: ObjectId,
+    pub component: Component,
+    /// Average position of its live edges' midpoints, in axial coordinates.
+    pub centroid: (f64, f64),
+    /// How far the centroid moves per generation, in axial coordinates.
+    ///
+    /// This is averaged over everything since the object last merged or split, so
+    /// oscillating spaceships settle down to their real speed over a few periods.
+    pub velocity: (f64, f64),
+    /// The generation it got its ID.
+    pub born: u64,
+    /// The generation and centroid the velocity is measured from.
+    since: (u64, (f64, f64)),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EventKind {
+    /// A new object showed up out of nowhere.
+    Born(ObjectId),
+    /// An object disappeared without turning into anything.
+    Died(ObjectId),
+    /// Several objects came together into one. `into` might be one of `from`, if it got
+    /// to keep its ID.
+    Merged { from: Vec<ObjectId>, into: ObjectId },
+    /// One object turned into several. One of `into` might be `from`.
+    Split { from: ObjectId, into: Vec<ObjectId> },
+}
+
+/// Something that happened to go from the generation before to `generation`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Event {
+    pub generation: u64,
+    pub kind: EventKind,
+}
+
+pub struct Tracker {
+    neighbors: NeighborRegion,
+    adjacency: Adjacency,
+    generation: u64,
+    objects: Vec<TrackedObject>,
+    next_id: ObjectId,
+    events: Vec<Event>,
+}
+
+impl Tracker {
+    /// Start following the objects on `board`, splitting it up by the rule's
+    /// neighborhood.
+    pub fn new(board: &Board, rule: Rule) -> Self {
+        Self::with_adjacency(board, rule, Adjacency::Neighbors(rule.neighbors))
+    }
+
+    /// Start following the objects on `board`, splitting it up some other way.
+    pub fn with_adjacency(board: &Board, rule: Rule, adjacency: Adjacency) -> Self {
+        let mut out = Self {
+            neighbors: rule.neighbors,
+            adjacency,
+            generation: 0,
+            objects: Vec::new(),
+            next_id: 0,
+            events: Vec::new(),
+        };
+        out.objects = out
+            .components(board)
+            .into_iter()
+            .map(|component| out.new_object(component))
+            .collect();
+        out
+    }
+
+    /// How many times [`Tracker::update`] has been called.
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// The objects there are now, sorted by bounding box.
+    pub fn objects(&self) -> &[TrackedObject] {
+        &self.objects
+    }
+
+    pub fn object(&self, id: ObjectId) -> Option<&TrackedObject> {
+        self.objects.iter().find(|object| object.id == id)
+    }
+
+    /// Everything that's happened so far, oldest first. The objects there were to start
+    /// with don't get `Born` events.
+    pub fn events(&self) -> &[Event] {
+        &self.events
+    }
+
+    /// Move on to `board`, which has to be the generation right after the last one,
+    /// and return what happened.
+    pub fn update(&mut self, board: &Board) -> &[Event] {
+        self.generation += 1;
+        let first_event = self.events.len();
+        let components = self.components(board);
+
+        // What each old object's live edges could have affected
+        let mut reach = AHashMap::new();
+        for (idx, object) in self.objects.iter().enumerate() {
+            for (pos, alive) in object.component.pattern.iter() {
+                if alive != Aliveness::Alive {
+                    continue;
+                }
+                reach.entry(pos).or_insert_with(Vec::new).push(idx);
+                for neighbor in self.neighbors.neighbors(pos) {
+                    reach.entry(neighbor).or_insert_with(Vec::new).push(idx);
+                }
+            }
+        }
+        // How many of each new component's edges each old object reaches
+        let mut overlaps = AHashMap::<(usize, usize), usize>::new();
+        for (new, component) in components.iter().enumerate() {
+            for (pos, _) in component.pattern.iter() {
+                let mut olds = reach.get(&pos).cloned().unwrap_or_default();
+                olds.sort_unstable();
+                olds.dedup();
+                for old in olds {
+                    *overlaps.entry((old, new)).or_default() += 1;
+                }
+            }
+        }
+
+        // Biggest overlaps get first pick of IDs, with older IDs winning ties
+        let mut links = overlaps.into_iter().collect::<Vec<_>>();
+        links.sort_by_key(|&((old, new), overlap)| {
+            (std::cmp::Reverse(overlap), self.objects[old].id, new)
+        });
+        let mut heir = vec![None; self.objects.len()];
+        let mut inherited = vec![None; components.len()];
+        let mut main_child = vec![None; self.objects.len()];
+        let mut main_parent = vec![None; components.len()];
+        for &((old, new), _) in links.iter() {
+            main_child[old].get_or_insert(new);
+            main_parent[new].get_or_insert(old);
+            if heir[old].is_none() && inherited[new].is_none() {
+                heir[old] = Some(new);
+                inherited[new] = Some(old);
+            }
+        }
+        // Objects that just pass close by each other overlap a little too, so only count
+        // the ones they overlap with the most
+        let mut parents = vec![Vec::new(); components.len()];
+        for (old, new) in main_child.iter().enumerate() {
+            if let Some(new) = *new {
+                parents[new].push(old);
+            }
+        }
+        let mut children = vec![Vec::new(); self.objects.len()];
+        for (new, old) in main_parent.iter().enumerate() {
+            if let Some(old) = *old {
+                children[old].push(new);
+            }
+        }
+
+        let mut objects = Vec::with_capacity(components.len());
+        for (new, component) in components.into_iter().enumerate() {
+            let object = match inherited[new] {
+                Some(old) => {
+                    let mut object = TrackedObject {
+                        component,
+                        ..self.objects[old].clone()
+                    };
+                    object.centroid = centroid(&object.component);
+                    // It's a different object now, so its old speed doesn't mean much
+                    if parents[new].len() > 1 || children[old].len() > 1 {
+                        object.since = (self.generation, object.centroid);
+                    }
+                    object.velocity = velocity(&object, self.generation);
+                    object
+                }
+                None => self.new_object(component),
+            };
+            objects.push(object);
+        }
+
+        let ids = |olds: &[usize], objects: &[TrackedObject]| {
+            let mut ids = olds.iter().map(|&idx| objects[idx].id).collect::<Vec<_>>();
+            ids.sort_unstable();
+            ids
+        };
+        for (old, object) in self.objects.iter().enumerate() {
+            let kind = match children[old].len() {
+                _ if main_child[old].is_none() => EventKind::Died(object.id),
+                0 | 1 => continue,
+                _ => EventKind::Split {
+                    from: object.id,
+                    into: ids(&children[old], &objects),
+                },
+            };
+            self.events.push(Event {
+                generation: self.generation,
+                kind,
+            });
+        }
+        for (new, object) in objects.iter().enumerate() {
+            let kind = match parents[new].len() {
+                _ if main_parent[new].is_none() => EventKind::Born(object.id),
+                0 | 1 => continue,
+                _ => EventKind::Merged {
+                    from: ids(&parents[new], &self.objects),
+                    into: object.id,
+                },
+            };
+            self.events.push(Event {
+                generation: self.generation,
+                kind,
+            });
+        }
+
+        self.objects = objects;
+        &self.events[first_event..]
+    }
+
+    /// Barren edges hold objects together, but pieces with nothing alive are about to
+    /// vanish.
+    fn components(&self, board: &Board) -> Vec<Component> {
+        let mut components = board.components(self.adjacency, true);
+        components.retain(|component| component.population > 0);
+        components
+    }
+
+    fn new_object(&mut self, component: Component) -> TrackedObject {
+        let id = self.next_id;
+        self.next_id += 1;
+        let centroid = centroid(&component);
+        TrackedObject {
+            id,
+            component,
+            centroid,
+            velocity: (0.0, 0.0),
+            born: self.generation,
+            since: (self.generation, centroid),
+        }
+    }
+}
+
+fn centroid(component: &Component) -> (f64, f64) {
+    let (mut x, mut y, mut count) = (0.0, 0.0, 0);
+    for (pos, alive) in component.pattern.iter() {
+        if alive != Aliveness::Alive {
+            continue;
+        }
+        let across = pos.coord() + pos.dir();
+        x += (pos.coord().x + across.x) as f64 / 2.0;
+        y += (pos.coord().y + across.y) as f64 / 2.0;
+        count += 1;
+    }
+    let count = count.max(1) as f64;
+    (x / count, y / count)
+}
+
+fn velocity(object: &TrackedObject, generation: u64) -> (f64, f64) {
+    let (since, (x, y)) = object.since;
+    if generation <= since {
+        return (0.0, 0.0);
+    }
+    let elapsed = (generation - since) as f64;
+    (
+        (object.centroid.0 - x) / elapsed,
+        (object.centroid.1 - y) / elapsed,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        code,
+        collision::{self, Setup},
+        math::HexCoord,
+    };
+
+    #[test]
+    fn gliders_keep_their_id() {
+        let rule: Rule = "B2/S3/@8*".parse().unwrap();
+        let table = rule.compile();
+        let mut board = code::decode_pattern("069z1a6z66").unwrap().to_board();
+        let mut tracker = Tracker::new(&board, rule);
+        for _ in 0..20 {
+            board.apply_table(&table);
+            tracker.update(&board);
+            assert_eq!(tracker.objects().len(), 1);
+            assert_eq!(tracker.objects()[0].id, 0);
+        }
+        assert!(tracker.events().is_empty());
+        // It moves one hex every two generations
+        let (x, y) = tracker.objects()[0].velocity;
+        assert!(x.abs() < 0.05 && (y - 0.5).abs() < 0.05, "{:?}", (x, y));
+    }
+
+    #[test]
+    fn crashes_merge_and_split() {
+        let rule: Rule = "B2/S346/@8*".parse().unwrap();
+        let table = rule.compile();
+        let glider = code::decode_pattern("069z1a6z66").unwrap();
+        let still = code::decode_pattern("0kikiz2o88iz2iioz286").unwrap();
+        let setup = Setup {
+            offset: HexCoord::new(-1, -7),
+            phase: 0,
+        };
+        let mut board = collision::setup_board(&still, &glider, rule, setup);
+        let mut tracker = Tracker::new(&board, rule);
+        assert_eq!(tracker.objects().len(), 2);
+        for _ in 0..40 {
+            board.apply_table(&table);
+            tracker.update(&board);
+        }
+
+        let kinds = tracker
+            .events()
+            .iter()
+            .map(|event| event.kind.clone())
+            .collect::<Vec<_>>();
+        // The glider runs into the still life, and then bits break off what's left
+        let EventKind::Merged { from, into: merged } = &kinds[0] else {
+            panic!("{:?}", kinds[0]);
+        };
+        assert_eq!(*from, vec![0, 1]);
+        assert!(kinds.iter().any(|kind| match kind {
+            EventKind::Split { from, into } => from == merged && into.contains(merged),
+            _ => false,
+        }));
+    }
+}
@@ -0,0 +1,285 @@
+//! Rules compiled down to lookup tables.
+//!
+//! Stepping the board looks at every edge that might change, gathers up which of its
+//! neighbors are alive into a bitfield (the "configuration"), and looks up what the edge
+//! turns into. Plain birth/survive rules only care about how many bits are set, but
+//! anything that can be written as a function of the configuration can be plugged in
+//! with [`TransitionTable::from_fn`].
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use ahash::AHashSet;
+
+use crate::{
+    math::{Aliveness, EdgePos, HexCoord, RestrictedHexDir},
+    NeighborRegion, Rule,
+};
+
+/// Neighborhoods with at most this many neighbors get compiled into a table indexed by
+/// the whole configuration; bigger ones are indexed by neighbor count.
+pub const MAX_CONFIGURATION_NEIGHBORS: u32 = 8;
+
+/// What a table was built from. Tables with the same key step boards the same way, so
+/// this is a cheap way to tell whether the table changed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TableKey {
+    Rule(Rule),
+    /// Built by [`TransitionTable::from_fn`]. Every one of those gets its own number.
+    Custom(u64),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransitionTable {
+    key: TableKey,
+    neighbors: NeighborRegion,
+    /// For each edge direction, the offsets and directions of the edges that count it as a
+    /// neighbor. The `i`th one is bit `i` of the configuration.
+    incoming: [Vec<(HexCoord, RestrictedHexDir)>; 3],
+    lookup: Lookup,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Lookup {
+    /// Indexed by `state * (neighbor count + 1) + live neighbors`.
+    Count(Vec<Aliveness>),
+    /// Indexed by `(edge * 3 + state) << neighbor count | configuration`.
+    Configuration(Vec<Aliveness>),
+}
+
+impl TransitionTable {
+    pub fn from_rule(rule: Rule) -> Self {
+        let incoming = incoming_edges(rule.neighbors);
+        let count = rule.neighbors.count();
+        let lookup = if count <= MAX_CONFIGURATION_NEIGHBORS {
+            let mut table = Vec::with_capacity(9 << count);
+            for _edge in RestrictedHexDir::all() {
+                for state in [Aliveness::Dead, Aliveness::Barren, Aliveness::Alive] {
+                    for config in 0u32..(1 << count) {
+                        table.push(rule.transition(state, config.count_ones() as u8));
+                    }
+                }
+            }
+            Lookup::Configuration(table)
+        } else {
+            let mut table = Vec::with_capacity(3 * (count as usize + 1));
+            for state in [Aliveness::Dead, Aliveness::Barren, Aliveness::Alive] {
+                for live in 0..=count {
+                    table.push(rule.transition(state, live as u8));
+                }
+            }
+            Lookup::Count(table)
+        };
+        Self {
+            key: TableKey::Rule(rule),
+            neighbors: rule.neighbors,
+            incoming,
+            lookup,
+        }
+    }
+
+    /// Compile an arbitrary function of the edge direction, its current state, and its
+    /// configuration into a table.
+    ///
+    /// This always builds a full configuration table, no matter how big the neighborhood is.
+    pub fn from_fn(
+        neighbors: NeighborRegion,
+        mut f: impl FnMut(RestrictedHexDir, Aliveness, u32) -> Aliveness,
+    ) -> Self {
+        let incoming = incoming_edges(neighbors);
+        let count = neighbors.count();
+        let mut table = Vec::with_capacity(9 << count);
+        for edge in RestrictedHexDir::all() {
+            for state in [Aliveness::Dead, Aliveness::Barren, Aliveness::Alive] {
+                for config in 0u32..(1 << count) {
+                    table.push(f(edge, state, config));
+                }
+            }
+        }
+        static NEXT_CUSTOM: AtomicU64 = AtomicU64::new(0);
+        Self {
+            key: TableKey::Custom(NEXT_CUSTOM.fetch_add(1, Ordering::Relaxed)),
+            neighbors,
+            incoming,
+            lookup: Lookup::Configuration(table),
+        }
+    }
+
+    pub fn key(&self) -> TableKey {
+        self.key
+    }
+
+    pub fn neighbors(&self) -> NeighborRegion {
+        self.neighbors
+    }
+
+    /// The offsets and directions of the edges that make up the configuration of an edge
+    /// in the given direction, in bit order.
+    pub fn incoming(&self, edge: RestrictedHexDir) -> &[(HexCoord, RestrictedHexDir)] {
+        &self.incoming[edge as usize]
+    }
+
+    /// The edges that make up the configuration of `pos`, in bit order.
+    pub fn sources(&self, pos: EdgePos) -> impl Iterator<Item = EdgePos> + '_ {
+        self.incoming[pos.edge() as usize]
+            .iter()
+            .map(move |&(offset, edge)| EdgePos::new_raw(pos.coord() + offset, edge))
+    }
+
+    /// Edges outside `inside` that have a neighbor in it, around the given hexes.
+    ///
+    /// Something that has to stay inside still needs these checked, since they'd be
+    /// born if the rule says so.
+    pub fn boundary(&self, coords: &[HexCoord], inside: impl Fn(EdgePos) -> bool) -> Vec<EdgePos> {
+        let mut out = Vec::new();
+        let mut seen = AHashSet::new();
+        for &coord in coords {
+            for near in coord.range_iter(2 * self.reach() + 1) {
+                for edge in RestrictedHexDir::all() {
+                    let pos = EdgePos::new_raw(near, edge);
+                    if !inside(pos) && self.sources(pos).any(&inside) && seen.insert(pos) {
+                        out.push(pos);
+                    }
+                }
+            }
+        }
+        out
+    }
+
+    /// Furthest away, in axial coordinates, that any neighbor can be.
+    pub fn reach(&self) -> i64 {
+        self.incoming
+            .iter()
+            .flatten()
+            .map(|(offset, _)| offset.x.abs().max(offset.y.abs()))
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Does empty space stay empty?
+    pub fn is_quiescent(&self) -> bool {
+        RestrictedHexDir::all()
+            .into_iter()
+            .all(|edge| self.next(edge, Aliveness::Dead, 0) == Aliveness::Dead)
+    }
+
+    /// Pack which of the edge's neighbors are alive into a configuration.
+    pub fn configuration(
+        &self,
+        pos: EdgePos,
+        mut liveness: impl FnMut(EdgePos) -> Aliveness,
+    ) -> u32 {
+        let mut config = 0;
+        for (idx, source) in self.sources(pos).enumerate() {
+            if liveness(source) == Aliveness::Alive {
+                config |= 1 << idx;
+            }
+        }
+        config
+    }
+
+    /// What an edge becomes given its direction, current state and configuration.
+    pub fn next(&self, edge: RestrictedHexDir, here: Aliveness, config: u32) -> Aliveness {
+        match &self.lookup {
+            Lookup::Count(table) => {
+                let stride = self.neighbors.count() as usize + 1;
+                table[here as usize * stride + config.count_ones() as usize]
+            }
+            Lookup::Configuration(table) => {
+                let idx = ((edge as usize * 3 + here as usize) << self.neighbors.count())
+                    | config as usize;
+                table[idx]
+            }
+        }
+    }
+}
+
+/// For each edge direction, find where the edges that count it as a neighbor are.
+fn incoming_edges(neighbors: NeighborRegion) -> [Vec<(HexCoord, RestrictedHexDir)>; 3] {
+    let origin = HexCoord::new(0, 0);
+    let mut out: [Vec<_>; 3] = Default::default();
+    for src in RestrictedHexDir::all() {
+        for neighbor in neighbors.neighbors(EdgePos::new_raw(origin, src)) {
+            out[neighbor.edge() as usize].push((origin - neighbor.coord(), src));
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evolve::ALL_NEIGHBORHOODS;
+
+    const STATES: [Aliveness; 3] = [Aliveness::Dead, Aliveness::Barren, Aliveness::Alive];
+
+    #[test]
+    fn tables_match_the_rule() {
+        for neighbors in ALL_NEIGHBORHOODS {
+            let count = neighbors.count();
+            // Every count gets looked up on its own, so masks with one bit set (and none
+            // and all of them) cover everything
+            let full = (1 << (count + 1)) - 1;
+            let masks = (0..=count)
+                .map(|bit| 1 << bit)
+                .chain([0, full])
+                .collect::<Vec<u32>>();
+            for &birth in masks.iter() {
+                for &survive in masks.iter() {
+                    let rule = Rule::new_raw(birth, survive, neighbors);
+                    let table = TransitionTable::from_rule(rule);
+                    for edge in RestrictedHexDir::all() {
+                        for here in STATES {
+                            for config in 0u32..(1 << count) {
+                                assert_eq!(
+                                    table.next(edge, here, config),
+                                    rule.transition(here, config.count_ones() as u8),
+                                    "{} {:?} {:?} {:b}",
+                                    rule,
+                                    edge,
+                                    here,
+                                    config
+                                );
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn configurations_are_the_neighbors() {
+        let pos = |coord, edge| EdgePos::new_raw(coord, edge);
+        for neighbors in ALL_NEIGHBORHOODS {
+            let table = TransitionTable::from_rule(Rule::new_raw(0, 0, neighbors));
+            for edge in RestrictedHexDir::all() {
+                let here = pos(HexCoord::new(0, 0), edge);
+                // Everything that has this edge as a neighbor, by brute force
+                let mut expected = HexCoord::new(0, 0)
+                    .range_iter(3)
+                    .flat_map(|coord| RestrictedHexDir::all().map(|edge| pos(coord, edge)))
+                    .filter(|&other| neighbors.neighbors(other).contains(&here))
+                    .map(|other| (other.coord().x, other.coord().y, other.edge() as u8))
+                    .collect::<Vec<_>>();
+                expected.sort_unstable();
+
+                let mut found = Vec::new();
+                for (bit, &(offset, from)) in table.incoming(edge).iter().enumerate() {
+                    let source = pos(offset, from);
+                    let config = table.configuration(here, |other| {
+                        if other == source {
+                            Aliveness::Alive
+                        } else {
+                            Aliveness::Barren
+                        }
+                    });
+                    assert_eq!(config, 1 << bit);
+                    found.push((offset.x, offset.y, from as u8));
+                }
+                found.sort_unstable();
+                assert_eq!(found, expected, "{:?} {:?}", neighbors, edge);
+                assert_eq!(found.len(), neighbors.count() as usize);
+            }
+        }
+    }
+}
@@ -24,7 +24,13 @@ impl GameState {
         }
 
         draw_text(
-            &format!("{}; {:?}", self.rule, self.running),
+            &format!(
+                "{}; {:?}; gen {}; {:?}",
+                self.sim.rule(),
+                self.running,
+                self.sim.generation(),
+                self.sim.outcome()
+            ),
             12.0,
             12.0,
             16.0,
@@ -98,7 +104,7 @@ impl GameState {
 
     fn draw_edges(&self, coord: Coordinate<i64>) {
         let px = self.hex_to_screen(coord);
-        let edges = self.board.get_edges(coord).unwrap_or_default();
+        let edges = self.sim.board().get_edges(coord).unwrap_or_default();
         let mouse_edge = self.mouse_edge();
 
         if self.draw_mode.do_edges() {
@@ -6,6 +6,7 @@ use std::f32::consts::TAU;
 use hex2d::Direction;
 use hexlife::{
     math::{Aliveness, EdgePos, HexCoord},
+    simulation::Simulation,
     Board, NeighborRegion, Rule,
 };
 use macroquad::prelude::*;
@@ -19,8 +20,7 @@ const SUPER_MOVE_SPEED: f32 = 43.0;
 const SQRT_3: f32 = 1.7320508;
 
 struct GameState {
-    board: Board,
-    rule: Rule,
+    sim: Simulation,
     running: RunState,
 
     /// Allow click and drag for edges but prevent flickering
@@ -34,8 +34,10 @@ struct GameState {
 impl GameState {
     fn new() -> Self {
         Self {
-            board: Board::new(),
-            rule: Rule::new_raw(0b0000100, 0b0001000, NeighborRegion::EightCross),
+            sim: Simulation::new(
+                Board::new(),
+                Rule::new_raw(0b0000100, 0b0001000, NeighborRegion::EightCross),
+            ),
             running: RunState::Stopped,
 
             drag_state: None,
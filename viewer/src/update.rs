@@ -1,5 +1,6 @@
 use crate::{GameState, RunState, MOVE_SPEED, SUPER_MOVE_SPEED, SUPER_ZOOM_SPEED, ZOOM_SPEED};
 
+use hexlife::Board;
 use macroquad::prelude::*;
 
 impl GameState {
@@ -12,11 +13,11 @@ impl GameState {
         match self.running {
             RunState::Stopped => {}
             RunState::OneStep => {
-                self.board.apply_rule(self.rule);
+                self.sim.step();
                 self.running = RunState::Stopped;
             }
             RunState::Run => {
-                self.board.apply_rule(self.rule);
+                self.sim.step();
             }
         }
     }
@@ -61,17 +62,18 @@ impl GameState {
 
         if is_mouse_button_down(MouseButton::Left) {
             let mouse_edge = self.mouse_edge();
-            let state = self
-                .drag_state
-                .unwrap_or_else(|| self.board.get_liveness(mouse_edge).flip());
-            self.board.set_alive(mouse_edge, state);
+            let current = self.sim.board().get_liveness(mouse_edge);
+            let state = self.drag_state.unwrap_or_else(|| current.flip());
+            if current != state {
+                self.sim.edit(|board| board.set_alive(mouse_edge, state));
+            }
             self.drag_state = Some(state);
         } else {
             self.drag_state = None;
         }
 
         if is_key_pressed(KeyCode::R) && is_key_down(KeyCode::LeftShift) {
-            self.board.clear();
+            self.sim.set_board(Board::new());
         }
 
         if is_key_pressed(KeyCode::Enter) {